@@ -1,3 +1,5 @@
 fn main() {
+    // Migrations are embedded by sqlx::migrate!, so rebuild when they change
+    println!("cargo:rerun-if-changed=migrations");
     tauri_build::build()
 }
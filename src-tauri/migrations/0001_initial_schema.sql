@@ -0,0 +1,89 @@
+-- Schema as of the switch to versioned migrations. Databases created before
+-- then are brought up to this shape by the legacy upgrade in
+-- `initialize_database`, so everything here must stay IF NOT EXISTS.
+
+CREATE TABLE IF NOT EXISTS library_roots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL UNIQUE,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    watch BOOLEAN NOT NULL DEFAULT 1,
+    exclusions TEXT NOT NULL DEFAULT '[]',
+    online BOOLEAN NOT NULL DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS albums (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    root_id INTEGER REFERENCES library_roots (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    year TEXT,
+    genre TEXT,
+    cover_art_base64 TEXT,
+    song_count INTEGER DEFAULT 0,
+    total_duration REAL DEFAULT 0.0,
+    folder_path TEXT NOT NULL,
+    replaygain_album_gain REAL,
+    replaygain_album_peak REAL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(title, artist, folder_path)
+);
+
+CREATE TABLE IF NOT EXISTS songs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    album_id INTEGER NOT NULL,
+    root_id INTEGER REFERENCES library_roots (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    album TEXT NOT NULL,
+    genre TEXT,
+    duration REAL DEFAULT 0.0,
+    path TEXT NOT NULL UNIQUE,
+    lyrics_path TEXT,
+    album_artist TEXT,
+    year TEXT,
+    label TEXT,
+    track_number TEXT,
+    file_modified_time INTEGER,
+    file_size INTEGER,
+    format TEXT,
+    codec TEXT,
+    replaygain_track_gain REAL,
+    replaygain_track_peak REAL,
+    replaygain_album_gain REAL,
+    replaygain_album_peak REAL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (album_id) REFERENCES albums (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS scan_errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    root_id INTEGER REFERENCES library_roots (id) ON DELETE CASCADE,
+    path TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS queue_entries (
+    position INTEGER PRIMARY KEY,
+    song_id INTEGER NOT NULL,
+    play_order INTEGER NOT NULL,
+    FOREIGN KEY (song_id) REFERENCES songs (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS queue_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    current_entry INTEGER,
+    position REAL DEFAULT 0.0,
+    repeat_mode TEXT NOT NULL DEFAULT 'off',
+    shuffled BOOLEAN NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_albums_artist_title ON albums(artist, title);
+CREATE INDEX IF NOT EXISTS idx_songs_album_id ON songs(album_id);
+CREATE INDEX IF NOT EXISTS idx_songs_path ON songs(path);
+CREATE INDEX IF NOT EXISTS idx_songs_root_id ON songs(root_id);
+CREATE INDEX IF NOT EXISTS idx_albums_root_id ON albums(root_id);
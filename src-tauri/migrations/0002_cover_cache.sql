@@ -0,0 +1,15 @@
+-- Cover art moves out of `albums.cover_art_base64` into an on-disk cache of
+-- images named by their SHA-256. Existing inline covers are moved into the
+-- cache, and the old column dropped, by `covers::move_inline_covers`.
+
+CREATE TABLE covers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    hash TEXT NOT NULL UNIQUE,
+    mime_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE albums ADD COLUMN cover_id INTEGER REFERENCES covers (id) ON DELETE SET NULL;
+
+CREATE INDEX idx_albums_cover_id ON albums (cover_id);
@@ -0,0 +1,14 @@
+-- Image dimensions, so the largest of several candidate covers can win, and
+-- the back covers, booklet scans and artist photos found next to an album.
+
+ALTER TABLE covers ADD COLUMN width INTEGER;
+ALTER TABLE covers ADD COLUMN height INTEGER;
+
+CREATE TABLE album_artwork (
+    album_id INTEGER NOT NULL REFERENCES albums (id) ON DELETE CASCADE,
+    cover_id INTEGER NOT NULL REFERENCES covers (id),
+    kind TEXT NOT NULL,
+    PRIMARY KEY (album_id, cover_id)
+);
+
+CREATE INDEX idx_album_artwork_cover_id ON album_artwork (cover_id);
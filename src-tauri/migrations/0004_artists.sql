@@ -0,0 +1,53 @@
+-- Artists as rows of their own, linked to the songs they perform on and the
+-- albums they are credited with. Names match case-insensitively. The sort
+-- name rule here must match `artists::sort_name`.
+
+CREATE TABLE artists (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    sort_name TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE song_artists (
+    song_id INTEGER NOT NULL REFERENCES songs (id) ON DELETE CASCADE,
+    artist_id INTEGER NOT NULL REFERENCES artists (id) ON DELETE CASCADE,
+    PRIMARY KEY (song_id, artist_id)
+);
+
+CREATE TABLE album_artists (
+    album_id INTEGER NOT NULL REFERENCES albums (id) ON DELETE CASCADE,
+    artist_id INTEGER NOT NULL REFERENCES artists (id) ON DELETE CASCADE,
+    PRIMARY KEY (album_id, artist_id)
+);
+
+CREATE INDEX idx_song_artists_artist_id ON song_artists (artist_id);
+CREATE INDEX idx_album_artists_artist_id ON album_artists (artist_id);
+CREATE INDEX idx_artists_sort_name ON artists (sort_name COLLATE NOCASE);
+
+INSERT INTO artists (name, sort_name)
+SELECT
+    name,
+    CASE
+        WHEN name LIKE 'The %' THEN substr(name, 5) || ', ' || substr(name, 1, 3)
+        WHEN name LIKE 'An %' THEN substr(name, 4) || ', ' || substr(name, 1, 2)
+        WHEN name LIKE 'A %' THEN substr(name, 3) || ', ' || substr(name, 1, 1)
+        ELSE name
+    END
+FROM (
+    SELECT trim(artist) AS name FROM songs
+    UNION
+    SELECT trim(artist) FROM albums
+)
+WHERE name != ''
+ON CONFLICT (name) DO NOTHING;
+
+INSERT OR IGNORE INTO song_artists (song_id, artist_id)
+SELECT songs.id, artists.id
+FROM songs
+JOIN artists ON artists.name = trim(songs.artist);
+
+INSERT OR IGNORE INTO album_artists (album_id, artist_id)
+SELECT albums.id, artists.id
+FROM albums
+JOIN artists ON artists.name = trim(albums.artist);
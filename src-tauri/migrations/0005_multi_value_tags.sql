@@ -0,0 +1,30 @@
+-- Genres get rows of their own, linked to songs like artists are. Existing
+-- genre strings are carried over as they are. Tags with several values were
+-- only partly read before, so every file is read again on the next scan,
+-- which also splits the names.
+
+CREATE TABLE genres (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE song_genres (
+    song_id INTEGER NOT NULL REFERENCES songs (id) ON DELETE CASCADE,
+    genre_id INTEGER NOT NULL REFERENCES genres (id) ON DELETE CASCADE,
+    PRIMARY KEY (song_id, genre_id)
+);
+
+CREATE INDEX idx_song_genres_genre_id ON song_genres (genre_id);
+
+INSERT INTO genres (name)
+SELECT DISTINCT trim(genre) FROM songs
+WHERE trim(genre) != ''
+ON CONFLICT (name) DO NOTHING;
+
+INSERT OR IGNORE INTO song_genres (song_id, genre_id)
+SELECT songs.id, genres.id
+FROM songs
+JOIN genres ON genres.name = trim(songs.genre);
+
+UPDATE songs SET file_modified_time = NULL;
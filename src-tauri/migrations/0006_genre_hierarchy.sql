@@ -0,0 +1,6 @@
+-- Genres can be filed under a broader one, so "Doom Metal" shows up when
+-- browsing "Metal". Deleting a parent leaves its subgenres at the top level.
+
+ALTER TABLE genres ADD COLUMN parent_id INTEGER REFERENCES genres (id) ON DELETE SET NULL;
+
+CREATE INDEX idx_genres_parent_id ON genres (parent_id);
@@ -0,0 +1,90 @@
+-- Full-text indexes over songs, albums and artists, ignoring case and
+-- diacritics, one per kind with the rowid of each row being the id of what it
+-- indexes so rows can be found without scanning the index. The scanner writes
+-- the rows of songs and albums; artists keep their own up to date, and rows go
+-- when what they index is deleted. Lyrics come from files only, so every file
+-- is read again on the next scan.
+
+CREATE VIRTUAL TABLE song_search USING fts5 (
+    title,
+    artist,
+    album_artist,
+    album,
+    genre,
+    label,
+    lyrics,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE album_search USING fts5 (
+    title,
+    album_artist,
+    genre,
+    label,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE VIRTUAL TABLE artist_search USING fts5 (
+    name,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER song_search_deleted AFTER DELETE ON songs BEGIN
+    DELETE FROM song_search WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER album_search_deleted AFTER DELETE ON albums BEGIN
+    DELETE FROM album_search WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER artist_search_added AFTER INSERT ON artists BEGIN
+    INSERT INTO artist_search (rowid, name) VALUES (new.id, new.name);
+END;
+
+CREATE TRIGGER artist_search_deleted AFTER DELETE ON artists BEGIN
+    DELETE FROM artist_search WHERE rowid = old.id;
+END;
+
+INSERT INTO song_search (rowid, title, artist, album_artist, album, genre, label)
+SELECT
+    songs.id, songs.title,
+    (
+        SELECT group_concat(artists.name, ' / ') FROM song_artists
+        JOIN artists ON artists.id = song_artists.artist_id
+        WHERE song_artists.song_id = songs.id
+    ),
+    (
+        SELECT group_concat(artists.name, ' / ') FROM album_artists
+        JOIN artists ON artists.id = album_artists.artist_id
+        WHERE album_artists.album_id = songs.album_id
+    ),
+    songs.album,
+    (
+        SELECT group_concat(genres.name, ' / ') FROM song_genres
+        JOIN genres ON genres.id = song_genres.genre_id
+        WHERE song_genres.song_id = songs.id
+    ),
+    songs.label
+FROM songs;
+
+INSERT INTO album_search (rowid, title, album_artist, genre, label)
+SELECT
+    albums.id, albums.title,
+    (
+        SELECT group_concat(artists.name, ' / ') FROM album_artists
+        JOIN artists ON artists.id = album_artists.artist_id
+        WHERE album_artists.album_id = albums.id
+    ),
+    (
+        SELECT group_concat(DISTINCT genres.name) FROM songs
+        JOIN song_genres ON song_genres.song_id = songs.id
+        JOIN genres ON genres.id = song_genres.genre_id
+        WHERE songs.album_id = albums.id
+    ),
+    (SELECT label FROM songs WHERE songs.album_id = albums.id AND label IS NOT NULL LIMIT 1)
+FROM albums;
+
+INSERT INTO artist_search (rowid, name)
+SELECT id, name FROM artists;
+
+UPDATE songs SET file_modified_time = NULL;
@@ -0,0 +1,4 @@
+-- Songs remember when their loudness was last analysed, so files that can't
+-- be decoded or measured aren't analysed again after every scan.
+
+ALTER TABLE songs ADD COLUMN loudness_analyzed_at DATETIME;
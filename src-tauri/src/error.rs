@@ -0,0 +1,80 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Errors returned by commands and the scanner. They reach the frontend as
+/// `{ code, message }`, where `code` is stable and safe to match on.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Database migration failed: {0}")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Couldn't read tags: {0}")]
+    Tag(#[from] lofty::error::LoftyError),
+    #[error("Couldn't process image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Invalid query: {0}")]
+    Query(#[from] crate::metadata::query::QueryError),
+    #[error("Scan already in progress")]
+    ScanInProgress,
+    #[error("No scan in progress")]
+    NoScanInProgress,
+    #[error("Loudness analysis already in progress")]
+    AnalysisInProgress,
+    #[error("No music folder configured")]
+    NoLibraryConfigured,
+    #[error("Settings error: {0}")]
+    Settings(String),
+    #[error("Couldn't watch folder: {0}")]
+    Watcher(String),
+    #[error("Player error: {0}")]
+    Player(String),
+    #[error("Background task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Database(_) => "database",
+            AppError::Migration(_) => "migration",
+            AppError::Io(_) => "io",
+            AppError::Tag(_) => "tag",
+            AppError::Image(_) => "image",
+            AppError::NotFound(_) => "not_found",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::Query(_) => "query",
+            AppError::ScanInProgress => "scan_in_progress",
+            AppError::NoScanInProgress => "no_scan_in_progress",
+            AppError::AnalysisInProgress => "analysis_in_progress",
+            AppError::NoLibraryConfigured => "no_library_configured",
+            AppError::Settings(_) => "settings",
+            AppError::Watcher(_) => "watcher",
+            AppError::Player(_) => "player",
+            AppError::Task(_) => "task",
+        }
+    }
+}
+
+impl From<tauri_plugin_store::Error> for AppError {
+    fn from(error: tauri_plugin_store::Error) -> Self {
+        AppError::Settings(error.to_string())
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("AppError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+pub type AppResult<T> = Result<T, AppError>;
@@ -1,12 +1,33 @@
+pub mod error;
 pub mod metadata;
 pub mod models;
+pub mod player;
 
-use metadata::scanner::{initialize_database, scan_music_folder};
-use models::{Album, AppState};
+use error::{AppError, AppResult};
+use metadata::covers::{load_cover, CoverCache};
+use metadata::genres;
+use metadata::loudness::analyze_missing_loudness;
+use metadata::query::Query;
+use metadata::roots;
+use metadata::scanner::{
+    default_scan_concurrency, initialize_database, scan_music_folder, ProgressReporter,
+    ScanControl, MAX_SCAN_CONCURRENCY,
+};
+use metadata::search::{self, SearchKind};
+use metadata::watcher::LibraryWatcher;
+use models::{
+    Album, AlbumArtwork, AppState, Artist, Genre, LibraryRoot, NormalizationMode, PlaybackState,
+    PlayerStatus, QueueSnapshot, RepeatMode, ScanError, ScanFailure, ScanProgress, SearchHit,
+    SearchResults, SnippetPart, SongInfo, TagSplitting,
+};
+use player::engine::{Player, PlayerCommand, MAX_CROSSFADE_SECONDS};
+use player::queue::load_queue;
+use sqlx::{QueryBuilder, SqlitePool};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::{AppHandle, Manager, State};
+use tauri::{http, AppHandle, Emitter, Manager, State, UriSchemeContext, UriSchemeResponder};
 use tauri_plugin_store::{Store, StoreBuilder};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -14,6 +35,7 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .register_asynchronous_uri_scheme_protocol("cover", serve_cover)
         .setup(|app| {
             let app_handle: AppHandle<tauri::Wry> = app.handle().clone();
 
@@ -51,14 +73,65 @@ pub fn run() {
                     std::fs::create_dir_all(parent)?;
                 }
 
-                let db_pool = initialize_database(&db_path).await?;
+                let covers = CoverCache::new(app_data_base_dir.join("covers"));
+                let db_pool = initialize_database(&db_path, &covers).await?;
+
+                // The single music folder setting became the first library root
+                if let Some(path) = store
+                    .get("music_folder_path")
+                    .and_then(|v| v.as_str().map(|s| s.to_string()))
+                {
+                    // Kept for the next launch if the folder isn't there now
+                    match roots::add_root(&db_pool, &path).await {
+                        Ok(_) => {
+                            store.delete("music_folder_path");
+                            store.save()?;
+                        }
+                        Err(e) => eprintln!("Couldn't add music folder {}: {}", path, e),
+                    }
+                }
+
+                let (queue, position) = load_queue(&db_pool).await?;
+
+                let player = Player::spawn(app_handle.clone(), db_pool.clone(), queue, position);
+                let crossfade = store
+                    .get("crossfade_duration")
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(0.0);
+                let _ = player.send(PlayerCommand::SetCrossfade(crossfade as f32));
+                let normalization = store
+                    .get("normalization_mode")
+                    .and_then(|v| v.as_str().map(NormalizationMode::parse))
+                    .unwrap_or_default();
+                let _ = player.send(PlayerCommand::SetNormalization(normalization));
+                let scan_concurrency = store
+                    .get("scan_concurrency")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize)
+                    .unwrap_or_else(default_scan_concurrency);
+                let tag_splitting = store
+                    .get("tag_splitting")
+                    .and_then(|v| serde_json::from_value(v).ok())
+                    .unwrap_or_default();
 
                 let app_state = AppState {
                     db_pool,
+                    covers,
                     is_scanning: Arc::new(RwLock::new(false)),
-                    scan_progress: Arc::new(RwLock::new(0.0)),
+                    scan_progress: Arc::new(RwLock::new(ScanProgress::default())),
+                    is_analyzing: Arc::new(RwLock::new(false)),
+                    scan_concurrency: Arc::new(RwLock::new(scan_concurrency)),
+                    tag_splitting: Arc::new(RwLock::new(tag_splitting)),
+                    scan_control: RwLock::new(ScanControl::default()),
+                    watchers: Mutex::new(HashMap::new()),
+                    library_writes: Arc::new(Mutex::new(())),
+                    player,
                 };
 
+                if let Err(e) = sync_watchers(&app_state, &app_handle).await {
+                    eprintln!("Failed to start library watchers: {}", e);
+                }
+
                 app.manage(store);
 
                 app.manage(app_state);
@@ -70,52 +143,310 @@ pub fn run() {
             )
         })
         .invoke_handler(tauri::generate_handler![
-            get_music_folder_path,
-            set_music_folder_path,
+            get_library_roots,
+            add_library_root,
+            remove_library_root,
+            set_library_root_options,
+            get_crossfade_duration,
+            set_crossfade_duration,
+            get_normalization_mode,
+            set_normalization_mode,
+            get_scan_concurrency,
+            set_scan_concurrency,
+            get_tag_splitting,
+            set_tag_splitting,
             scan_music_library,
+            scan_library_roots,
+            analyze_loudness,
             get_albums,
             get_album_songs,
+            get_album_artwork,
             get_scan_status,
             rescan_library,
+            cancel_scan,
+            pause_scan,
+            resume_scan,
+            get_scan_errors,
             search_albums,
-            get_album_by_id
+            search,
+            query_songs,
+            query_albums,
+            get_album_by_id,
+            get_artists,
+            get_artist_by_id,
+            get_artist_albums,
+            get_artist_tracks,
+            get_genres,
+            get_genre_albums,
+            add_genre,
+            set_genre_parent,
+            play_song,
+            pause,
+            resume,
+            stop,
+            seek,
+            set_volume,
+            get_player_status,
+            get_queue,
+            enqueue_album,
+            enqueue_songs,
+            move_queue_entry,
+            remove_from_queue,
+            clear_queue,
+            set_shuffle,
+            set_repeat_mode,
+            play_queue_index,
+            next_track,
+            previous_track
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+/// List the library roots
 #[tauri::command]
-async fn get_music_folder_path(
-    settings_store: State<'_, Store<tauri::Wry>>,
-) -> Result<Option<String>, String> {
-    let value = settings_store.get("music_folder_path".to_string());
-    Ok(value.and_then(|v| v.as_str().map(|s| s.to_string())))
+async fn get_library_roots(app_state: State<'_, AppState>) -> AppResult<Vec<LibraryRoot>> {
+    Ok(roots::load_roots(&app_state.db_pool).await?)
 }
 
+/// Add a folder to the library. It isn't scanned until asked to.
 #[tauri::command]
-async fn set_music_folder_path(
+async fn add_library_root(
     path: String,
-    settings_store: State<'_, Store<tauri::Wry>>,
-) -> Result<(), String> {
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> AppResult<LibraryRoot> {
+    let root = roots::add_root(&app_state.db_pool, &path).await?;
+    sync_watchers(&app_state, &app_handle).await?;
+    Ok(root)
+}
+
+/// Remove a folder from the library along with its songs and albums
+#[tauri::command]
+async fn remove_library_root(
+    root_id: i64,
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> AppResult<()> {
+    app_state.watchers.lock().await.remove(&root_id);
+    {
+        let _writing = app_state.library_writes.lock().await;
+        roots::remove_root(&app_state.db_pool, &app_state.covers, root_id).await?;
+    }
+    sync_watchers(&app_state, &app_handle).await
+}
+
+/// Change whether a root is scanned, whether it is watched, and which of its
+/// files are skipped (glob patterns relative to the root)
+#[tauri::command]
+async fn set_library_root_options(
+    root_id: i64,
+    enabled: bool,
+    watch: bool,
+    exclusions: Vec<String>,
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> AppResult<LibraryRoot> {
+    roots::set_root_options(&app_state.db_pool, root_id, enabled, watch, &exclusions).await?;
+
+    // The running watcher holds the old options
+    app_state.watchers.lock().await.remove(&root_id);
+    sync_watchers(&app_state, &app_handle).await?;
+
+    roots::get_root(&app_state.db_pool, root_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Library root {}", root_id)))
+}
+
+/// Start or stop watchers so that exactly the enabled, watched and online roots are watched
+async fn sync_watchers(app_state: &AppState, app_handle: &AppHandle) -> AppResult<()> {
+    let wanted: Vec<LibraryRoot> = roots::load_roots(&app_state.db_pool)
+        .await?
+        .into_iter()
+        .filter(|root| root.enabled && root.watch && root.online)
+        .collect();
+
+    let mut watchers = app_state.watchers.lock().await;
+    watchers.retain(|root_id, _| wanted.iter().any(|root| root.id == *root_id));
+
+    for root in wanted {
+        if watchers.contains_key(&root.id) {
+            continue;
+        }
+        let root_id = root.id;
+        let path = root.path.clone();
+        match LibraryWatcher::start(
+            root,
+            app_state.db_pool.clone(),
+            app_state.covers.clone(),
+            app_state.library_writes.clone(),
+            app_handle.clone(),
+        ) {
+            Ok(watcher) => {
+                watchers.insert(root_id, watcher);
+            }
+            Err(e) => eprintln!("Failed to watch {}: {}", path, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the crossfade length in seconds (0 when disabled)
+#[tauri::command]
+async fn get_crossfade_duration(
+    settings_store: State<'_, Arc<Store<tauri::Wry>>>,
+) -> AppResult<f32> {
+    let value = settings_store.get("crossfade_duration");
+    Ok(value.and_then(|v| v.as_f64()).unwrap_or(0.0) as f32)
+}
+
+/// Set the crossfade length in seconds, 0 to disable it
+#[tauri::command]
+async fn set_crossfade_duration(
+    seconds: f32,
+    settings_store: State<'_, Arc<Store<tauri::Wry>>>,
+    app_state: State<'_, AppState>,
+) -> AppResult<()> {
+    if !seconds.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Crossfade length must be a number of seconds, got {}",
+            seconds
+        )));
+    }
+    let seconds = seconds.clamp(0.0, MAX_CROSSFADE_SECONDS);
+    settings_store.set("crossfade_duration".to_string(), serde_json::json!(seconds));
+
+    settings_store.save()?;
+    app_state.player.send(PlayerCommand::SetCrossfade(seconds))
+}
+
+/// Get the loudness normalization mode
+#[tauri::command]
+async fn get_normalization_mode(
+    settings_store: State<'_, Arc<Store<tauri::Wry>>>,
+) -> AppResult<NormalizationMode> {
+    let value = settings_store.get("normalization_mode");
+    Ok(value
+        .and_then(|v| v.as_str().map(NormalizationMode::parse))
+        .unwrap_or_default())
+}
+
+/// Set the loudness normalization mode ("off", "track" or "album")
+#[tauri::command]
+async fn set_normalization_mode(
+    mode: NormalizationMode,
+    settings_store: State<'_, Arc<Store<tauri::Wry>>>,
+    app_state: State<'_, AppState>,
+) -> AppResult<()> {
     settings_store.set(
-        "music_folder_path".to_string(),
-        serde_json::Value::String(path),
+        "normalization_mode".to_string(),
+        serde_json::Value::String(mode.as_str().to_string()),
     );
 
-    settings_store.save().map_err(|e| e.to_string())?;
+    settings_store.save()?;
+    app_state.player.send(PlayerCommand::SetNormalization(mode))
+}
+
+/// Get how many files a scan reads at once
+#[tauri::command]
+async fn get_scan_concurrency(app_state: State<'_, AppState>) -> AppResult<usize> {
+    Ok(*app_state.scan_concurrency.read().await)
+}
+
+/// Set how many files a scan reads at once. Takes effect from the next scan.
+#[tauri::command]
+async fn set_scan_concurrency(
+    concurrency: usize,
+    settings_store: State<'_, Arc<Store<tauri::Wry>>>,
+    app_state: State<'_, AppState>,
+) -> AppResult<()> {
+    let concurrency = concurrency.clamp(1, MAX_SCAN_CONCURRENCY);
+    settings_store.set(
+        "scan_concurrency".to_string(),
+        serde_json::json!(concurrency),
+    );
+
+    settings_store.save()?;
+    *app_state.scan_concurrency.write().await = concurrency;
     Ok(())
 }
 
+/// Get how multi-value artist and genre tags are split into single names
+#[tauri::command]
+async fn get_tag_splitting(app_state: State<'_, AppState>) -> AppResult<TagSplitting> {
+    Ok(app_state.tag_splitting.read().await.clone())
+}
+
+/// Set how multi-value artist and genre tags are split. Every file is read
+/// again on the next scan so the new rules apply to the whole library.
+#[tauri::command]
+async fn set_tag_splitting(
+    splitting: TagSplitting,
+    settings_store: State<'_, Arc<Store<tauri::Wry>>>,
+    app_state: State<'_, AppState>,
+) -> AppResult<()> {
+    if *app_state.tag_splitting.read().await == splitting {
+        return Ok(());
+    }
+
+    settings_store.set("tag_splitting".to_string(), serde_json::json!(splitting));
+
+    settings_store.save()?;
+    *app_state.tag_splitting.write().await = splitting;
+    sqlx::query("UPDATE songs SET file_modified_time = NULL")
+        .execute(&app_state.db_pool)
+        .await?;
+    Ok(())
+}
+
+/// Add a folder to the library if needed and scan it
 #[tauri::command]
 async fn scan_music_library(
     folder_path: String,
     app_state: State<'_, AppState>,
     app_handle: AppHandle,
-) -> Result<(), String> {
+) -> AppResult<()> {
+    let root = roots::add_root(&app_state.db_pool, &folder_path).await?;
+    start_scan(&app_state, vec![root], app_handle).await
+}
+
+/// Scan the given library roots, or every enabled one
+#[tauri::command]
+async fn scan_library_roots(
+    root_ids: Option<Vec<i64>>,
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> AppResult<()> {
+    let roots: Vec<LibraryRoot> = roots::load_roots(&app_state.db_pool)
+        .await?
+        .into_iter()
+        .filter(|root| match &root_ids {
+            Some(root_ids) => root_ids.contains(&root.id),
+            None => root.enabled,
+        })
+        .collect();
+
+    if roots.is_empty() {
+        return Err(match root_ids {
+            Some(root_ids) => AppError::NotFound(format!("Library roots {:?}", root_ids)),
+            None => AppError::NoLibraryConfigured,
+        });
+    }
+
+    start_scan(&app_state, roots, app_handle).await
+}
+
+/// Scan `roots` one after another in the background
+async fn start_scan(
+    app_state: &AppState,
+    roots: Vec<LibraryRoot>,
+    app_handle: AppHandle,
+) -> AppResult<()> {
     {
         let is_scanning = app_state.is_scanning.read().await;
         if *is_scanning {
-            return Err("Scan already in progress".to_string());
+            return Err(AppError::ScanInProgress);
         }
     }
 
@@ -123,45 +454,202 @@ async fn scan_music_library(
         let mut is_scanning = app_state.is_scanning.write().await;
         *is_scanning = true;
         let mut progress = app_state.scan_progress.write().await;
-        *progress = 0.0;
+        *progress = ScanProgress {
+            is_scanning: true,
+            ..Default::default()
+        };
     }
 
     let db_pool = app_state.db_pool.clone();
+    let covers = app_state.covers.clone();
     let is_scanning = app_state.is_scanning.clone();
-    let scan_progress = app_state.scan_progress.clone();
+    let mut reporter = ProgressReporter::new(app_state.scan_progress.clone(), app_handle.clone());
+    let is_analyzing = app_state.is_analyzing.clone();
+    let concurrency = *app_state.scan_concurrency.read().await;
+    let splitting = app_state.tag_splitting.read().await.clone();
+    let control = ScanControl::default();
+    *app_state.scan_control.write().await = control.clone();
+    let library_writes = app_state.library_writes.clone();
 
     tokio::spawn(async move {
-        let result =
-            scan_music_folder(folder_path, db_pool, scan_progress.clone(), app_handle).await;
+        let writing = library_writes.lock().await;
+        let mut scanned_any = false;
+        for root in &roots {
+            if control.is_cancelled() {
+                break;
+            }
+
+            let result = scan_music_folder(
+                root,
+                db_pool.clone(),
+                &covers,
+                &splitting,
+                concurrency,
+                &control,
+                &mut reporter,
+            )
+            .await;
+
+            match result {
+                Ok(()) => scanned_any = true,
+                Err(e) => {
+                    eprintln!("Scan error in {}: {}", root.path, e);
+                    let _ = app_handle.emit(
+                        "scan_failed",
+                        ScanFailure {
+                            root_id: root.id,
+                            path: root.path.clone(),
+                            message: e.to_string(),
+                        },
+                    );
+                }
+            }
+        }
+        drop(writing);
 
         {
             let mut scanning = is_scanning.write().await;
             *scanning = false;
         }
+        reporter.finish().await;
 
-        if let Err(e) = result {
-            eprintln!("Scan error: {}", e);
+        let cancelled = control.is_cancelled();
+        if cancelled {
+            let _ = app_handle.emit("scan_cancelled", ());
+        } else {
+            let _ = app_handle.emit("scan_complete", ());
+        }
+
+        // Roots may have gone offline or come back
+        let app_state = app_handle.state::<AppState>();
+        if let Err(e) = sync_watchers(&app_state, &app_handle).await {
+            eprintln!("Failed to update library watchers: {}", e);
+        }
+
+        if scanned_any && !cancelled {
+            start_loudness_analysis(db_pool, is_analyzing, app_handle).await;
         }
     });
 
     Ok(())
 }
 
+/// Compute ReplayGain values for songs whose files carry none
+#[tauri::command]
+async fn analyze_loudness(app_state: State<'_, AppState>, app_handle: AppHandle) -> AppResult<()> {
+    if !start_loudness_analysis(
+        app_state.db_pool.clone(),
+        app_state.is_analyzing.clone(),
+        app_handle,
+    )
+    .await
+    {
+        return Err(AppError::AnalysisInProgress);
+    }
+    Ok(())
+}
+
+/// Run the loudness analysis in the background. Returns false if it is already running.
+async fn start_loudness_analysis(
+    db_pool: SqlitePool,
+    is_analyzing: Arc<RwLock<bool>>,
+    app_handle: AppHandle,
+) -> bool {
+    {
+        let mut analyzing = is_analyzing.write().await;
+        if *analyzing {
+            return false;
+        }
+        *analyzing = true;
+    }
+
+    tokio::spawn(async move {
+        let result = analyze_missing_loudness(db_pool, app_handle).await;
+
+        {
+            let mut analyzing = is_analyzing.write().await;
+            *analyzing = false;
+        }
+
+        if let Err(e) = result {
+            eprintln!("Loudness analysis error: {}", e);
+        }
+    });
+
+    true
+}
+
+/// Serve `cover://localhost/<id>` from the cover cache, or its thumbnail at
+/// `cover://localhost/<id>/<size>`. The image behind a URL never changes, so
+/// the webview may cache it for good.
+fn serve_cover(
+    ctx: UriSchemeContext<'_, tauri::Wry>,
+    request: http::Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app_handle = ctx.app_handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let response = match app_handle.try_state::<AppState>() {
+            Some(app_state) => {
+                let cover = match parse_cover_path(request.uri().path()) {
+                    Some((cover_id, size)) => {
+                        load_cover(&app_state.db_pool, &app_state.covers, cover_id, size).await
+                    }
+                    None => Err(AppError::InvalidInput("Not a cover URL".into())),
+                };
+                match cover {
+                    Ok((bytes, mime_type)) => http::Response::builder()
+                        .header(http::header::CONTENT_TYPE, mime_type)
+                        .header(http::header::CACHE_CONTROL, "max-age=31536000, immutable")
+                        .body(bytes),
+                    Err(e) => {
+                        eprintln!("Failed to serve {}: {}", request.uri(), e);
+                        let status = match e {
+                            AppError::NotFound(_) | AppError::InvalidInput(_) => {
+                                http::StatusCode::NOT_FOUND
+                            }
+                            _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+                        };
+                        http::Response::builder().status(status).body(Vec::new())
+                    }
+                }
+            }
+            None => http::Response::builder()
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body(Vec::new()),
+        };
+        responder.respond(response.unwrap_or_default());
+    });
+}
+
+/// Cover id and thumbnail size from a `cover` protocol path such as `/12/256`
+fn parse_cover_path(path: &str) -> Option<(i64, Option<u32>)> {
+    let mut parts = path.trim_start_matches('/').split('/');
+    let cover_id = parts.next()?.parse().ok()?;
+    let size = match parts.next() {
+        Some(size) => Some(size.parse().ok()?),
+        None => None,
+    };
+    match parts.next() {
+        Some(_) => None,
+        None => Some((cover_id, size)),
+    }
+}
+
 /// Get all albums from database
 #[tauri::command]
 async fn get_albums(
     app_state: State<'_, AppState>,
     limit: Option<u32>,
     offset: Option<u32>,
-) -> Result<Vec<Album>, String> {
-    use sqlx::Row;
-
+    cover_size: Option<u32>,
+) -> AppResult<Vec<Album>> {
     let limit = limit.unwrap_or(50);
     let offset = offset.unwrap_or(0);
 
     let query = r#"
         SELECT 
-            id, title, artist, year, genre, cover_art_base64, 
+            id, title, artist, year, genre, cover_id,
             song_count, total_duration, folder_path
         FROM albums 
         ORDER BY artist, title 
@@ -172,25 +660,12 @@ async fn get_albums(
         .bind(limit)
         .bind(offset)
         .fetch_all(&app_state.db_pool)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let albums = rows
-        .into_iter()
-        .map(|row| Album {
-            id: row.get("id"),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            year: row.get("year"),
-            genre: row.get("genre"),
-            cover_art_base64: row.get("cover_art_base64"),
-            song_count: row.get("song_count"),
-            total_duration: row.get("total_duration"),
-            folder_path: row.get("folder_path"),
-        })
-        .collect();
+        .await?;
 
-    Ok(albums)
+    Ok(rows
+        .iter()
+        .map(|row| Album::from_row(row, cover_size))
+        .collect())
 }
 
 /// Get songs for a specific album
@@ -198,13 +673,13 @@ async fn get_albums(
 async fn get_album_songs(
     album_id: i64,
     app_state: State<'_, AppState>,
-) -> Result<Vec<crate::models::SongInfo>, String> {
-    use sqlx::Row;
-
+) -> AppResult<Vec<SongInfo>> {
     let query = r#"
         SELECT 
-            id, title, artist, album, genre, duration, path, 
-            lyrics_path, album_artist, year, label, track_number
+            id, album_id, title, artist, album, genre, duration, path, 
+            lyrics_path, album_artist, year, label, track_number, format, codec,
+            replaygain_track_gain, replaygain_track_peak,
+            replaygain_album_gain, replaygain_album_peak
         FROM songs 
         WHERE album_id = ? 
         ORDER BY track_number, title
@@ -213,104 +688,234 @@ async fn get_album_songs(
     let rows = sqlx::query(query)
         .bind(album_id)
         .fetch_all(&app_state.db_pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
-    let songs = rows
-        .into_iter()
-        .map(|row| crate::models::SongInfo {
-            id: Some(row.get("id")),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            album: row.get("album"),
-            genre: row.get("genre"),
-            duration: row.get("duration"),
-            path: row.get("path"),
-            lyrics_path: row.get("lyrics_path"),
-            cover_art_base64: None, // Don't load cover art for individual songs
-            album_artist: row.get("album_artist"),
-            year: row.get("year"),
-            label: row.get("label"),
-            track_number: row.get("track_number"),
-        })
-        .collect();
+    Ok(rows.iter().map(SongInfo::from_row).collect())
+}
+
+/// Back covers, booklet scans and other pictures of an album besides its cover
+#[tauri::command]
+async fn get_album_artwork(
+    album_id: i64,
+    app_state: State<'_, AppState>,
+    cover_size: Option<u32>,
+) -> AppResult<Vec<AlbumArtwork>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT album_artwork.cover_id, album_artwork.kind, covers.width, covers.height
+        FROM album_artwork
+        JOIN covers ON covers.id = album_artwork.cover_id
+        WHERE album_artwork.album_id = ?
+        ORDER BY album_artwork.kind, album_artwork.cover_id
+        "#,
+    )
+    .bind(album_id)
+    .fetch_all(&app_state.db_pool)
+    .await?;
 
-    Ok(songs)
+    Ok(rows
+        .iter()
+        .map(|row| AlbumArtwork::from_row(row, cover_size))
+        .collect())
 }
 
 /// Get current scan status
 #[tauri::command]
-async fn get_scan_status(app_state: State<'_, AppState>) -> Result<(bool, f32), String> {
-    let is_scanning = *app_state.is_scanning.read().await;
-    let progress = *app_state.scan_progress.read().await;
-    Ok((is_scanning, progress))
+async fn get_scan_status(app_state: State<'_, AppState>) -> AppResult<ScanProgress> {
+    Ok(app_state.scan_progress.read().await.clone())
 }
 
-/// Rescan the music library
+/// Rescan every enabled library root
 #[tauri::command]
-async fn rescan_library(
-    app_state: State<'_, AppState>,
-    app_handle: AppHandle,
-    settings_store: State<'_, Store<tauri::Wry>>,
-) -> Result<(), String> {
-    // Get the saved folder path
-    let folder_path = match settings_store.get("music_folder_path".to_string()) {
-        Some(value) => value.as_str().map(|s| s.to_string()),
-        None => None,
-    };
+async fn rescan_library(app_state: State<'_, AppState>, app_handle: AppHandle) -> AppResult<()> {
+    scan_library_roots(None, app_state, app_handle).await
+}
+
+/// Stop the running scan after the files being read. What was read is kept.
+#[tauri::command]
+async fn cancel_scan(app_state: State<'_, AppState>) -> AppResult<()> {
+    if !*app_state.is_scanning.read().await {
+        return Err(AppError::NoScanInProgress);
+    }
+    app_state.scan_control.read().await.cancel();
+    Ok(())
+}
 
-    match folder_path {
-        Some(path) => scan_music_library(path, app_state, app_handle).await,
-        None => Err("No music folder path configured".to_string()),
+/// Pause the running scan before its next file
+#[tauri::command]
+async fn pause_scan(app_state: State<'_, AppState>, app_handle: AppHandle) -> AppResult<()> {
+    if !*app_state.is_scanning.read().await {
+        return Err(AppError::NoScanInProgress);
     }
+    app_state.scan_control.read().await.set_paused(true);
+    set_scan_paused(&app_state, &app_handle, true).await;
+    Ok(())
 }
 
-/// Search albums by title or artist
+/// Resume a paused scan
+#[tauri::command]
+async fn resume_scan(app_state: State<'_, AppState>, app_handle: AppHandle) -> AppResult<()> {
+    app_state.scan_control.read().await.set_paused(false);
+    if *app_state.is_scanning.read().await {
+        set_scan_paused(&app_state, &app_handle, false).await;
+    }
+    Ok(())
+}
+
+/// Record the pause state in the scan progress and let the frontend know
+async fn set_scan_paused(app_state: &AppState, app_handle: &AppHandle, paused: bool) {
+    let mut progress = app_state.scan_progress.write().await;
+    progress.paused = paused;
+    let _ = app_handle.emit("scan_progress", progress.clone());
+}
+
+/// Files the last scans couldn't read, for one root or all of them
+#[tauri::command]
+async fn get_scan_errors(
+    root_id: Option<i64>,
+    app_state: State<'_, AppState>,
+) -> AppResult<Vec<ScanError>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, root_id, path, kind, message, created_at
+        FROM scan_errors
+        WHERE ?1 IS NULL OR root_id = ?1
+        ORDER BY path
+        "#,
+    )
+    .bind(root_id)
+    .fetch_all(&app_state.db_pool)
+    .await?;
+
+    Ok(rows.iter().map(ScanError::from_row).collect())
+}
+
+/// Search albums by title, artist, genre or label, best matches first
 #[tauri::command]
 async fn search_albums(
     query: String,
     app_state: State<'_, AppState>,
     limit: Option<u32>,
-) -> Result<Vec<Album>, String> {
-    use sqlx::Row;
+    cover_size: Option<u32>,
+) -> AppResult<Vec<Album>> {
+    let Some(expression) = search::match_expression(&query) else {
+        return Ok(Vec::new());
+    };
+
+    let hits = search::find(
+        &app_state.db_pool,
+        SearchKind::Album,
+        &expression,
+        limit.unwrap_or(20),
+    )
+    .await?;
+    let album_ids: Vec<i64> = hits.into_iter().map(|(album_id, _)| album_id).collect();
+    Ok(fetch_albums_by_id(&app_state.db_pool, &album_ids, cover_size).await?)
+}
 
+/// Search songs, albums and artists at once: titles, artists, album artists,
+/// albums, genres, labels and lyrics. Each group holds up to `limit` results,
+/// best first, with the matched text highlighted in a snippet.
+#[tauri::command]
+async fn search(
+    query: String,
+    app_state: State<'_, AppState>,
+    limit: Option<u32>,
+    cover_size: Option<u32>,
+) -> AppResult<SearchResults> {
+    let Some(expression) = search::match_expression(&query) else {
+        return Ok(SearchResults::default());
+    };
+    let db_pool = &app_state.db_pool;
     let limit = limit.unwrap_or(20);
-    let search_query = format!("%{}%", query);
 
-    let sql = r#"
-        SELECT 
-            id, title, artist, year, genre, cover_art_base64, 
-            song_count, total_duration, folder_path
-        FROM albums 
-        WHERE title LIKE ? OR artist LIKE ?
-        ORDER BY artist, title 
-        LIMIT ?
-    "#;
+    let song_hits = search::find(db_pool, SearchKind::Song, &expression, limit).await?;
+    let album_hits = search::find(db_pool, SearchKind::Album, &expression, limit).await?;
+    let artist_hits = search::find(db_pool, SearchKind::Artist, &expression, limit).await?;
 
-    let rows = sqlx::query(sql)
-        .bind(&search_query)
-        .bind(&search_query)
-        .bind(limit)
-        .fetch_all(&app_state.db_pool)
-        .await
-        .map_err(|e| e.to_string())?;
+    let song_ids: Vec<i64> = song_hits.iter().map(|(id, _)| *id).collect();
+    let album_ids: Vec<i64> = album_hits.iter().map(|(id, _)| *id).collect();
+    let artist_ids: Vec<i64> = artist_hits.iter().map(|(id, _)| *id).collect();
 
-    let albums = rows
-        .into_iter()
-        .map(|row| Album {
-            id: row.get("id"),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            year: row.get("year"),
-            genre: row.get("genre"),
-            cover_art_base64: row.get("cover_art_base64"),
-            song_count: row.get("song_count"),
-            total_duration: row.get("total_duration"),
-            folder_path: row.get("folder_path"),
-        })
-        .collect();
+    let songs = fetch_songs_by_id(db_pool, &song_ids).await?;
+    let albums = fetch_albums_by_id(db_pool, &album_ids, cover_size).await?;
+    let artists = fetch_artists_by_id(db_pool, &artist_ids).await?;
+
+    Ok(SearchResults {
+        songs: with_snippets(songs, song_hits, |song| song.id.unwrap_or_default()),
+        albums: with_snippets(albums, album_hits, |album| album.id),
+        artists: with_snippets(artists, artist_hits, |artist| artist.id),
+    })
+}
+
+/// Songs matching a structured query such as
+/// `artist:"Radiohead" year:1995..2000 -label:XL`. See `Query` for the syntax.
+#[tauri::command]
+async fn query_songs(
+    query: String,
+    app_state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> AppResult<Vec<SongInfo>> {
+    let query = Query::parse(&query)?;
+
+    let mut sql = QueryBuilder::new(
+        r#"
+        SELECT
+            songs.id, songs.album_id, songs.title, songs.artist, songs.album, songs.genre,
+            songs.duration, songs.path, songs.lyrics_path, songs.album_artist, songs.year,
+            songs.label, songs.track_number, songs.format, songs.codec,
+            songs.replaygain_track_gain, songs.replaygain_track_peak,
+            songs.replaygain_album_gain, songs.replaygain_album_peak
+        FROM songs
+        WHERE 1
+        "#,
+    );
+    query.push_conditions(&mut sql);
+    sql.push(
+        " ORDER BY COALESCE(songs.album_artist, songs.artist), songs.album, \
+         CAST(songs.track_number AS INTEGER), songs.title LIMIT ",
+    )
+    .push_bind(limit.unwrap_or(500))
+    .push(" OFFSET ")
+    .push_bind(offset.unwrap_or(0));
+
+    let rows = sql.build().fetch_all(&app_state.db_pool).await?;
+    Ok(rows.iter().map(SongInfo::from_row).collect())
+}
+
+/// Albums with at least one song matching a structured query. See `Query`
+/// for the syntax.
+#[tauri::command]
+async fn query_albums(
+    query: String,
+    app_state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    cover_size: Option<u32>,
+) -> AppResult<Vec<Album>> {
+    let query = Query::parse(&query)?;
+
+    let mut sql = QueryBuilder::new(
+        r#"
+        SELECT
+            id, title, artist, year, genre, cover_id,
+            song_count, total_duration, folder_path
+        FROM albums
+        WHERE id IN (SELECT songs.album_id FROM songs WHERE 1
+        "#,
+    );
+    query.push_conditions(&mut sql);
+    sql.push(") ORDER BY artist, year, title LIMIT ")
+        .push_bind(limit.unwrap_or(100))
+        .push(" OFFSET ")
+        .push_bind(offset.unwrap_or(0));
 
-    Ok(albums)
+    let rows = sql.build().fetch_all(&app_state.db_pool).await?;
+    Ok(rows
+        .iter()
+        .map(|row| Album::from_row(row, cover_size))
+        .collect())
 }
 
 /// Get a specific album by ID
@@ -318,12 +923,11 @@ async fn search_albums(
 async fn get_album_by_id(
     album_id: i64,
     app_state: State<'_, AppState>,
-) -> Result<Option<Album>, String> {
-    use sqlx::Row;
-
+    cover_size: Option<u32>,
+) -> AppResult<Option<Album>> {
     let query = r#"
         SELECT 
-            id, title, artist, year, genre, cover_art_base64, 
+            id, title, artist, year, genre, cover_id,
             song_count, total_duration, folder_path
         FROM albums 
         WHERE id = ?
@@ -332,20 +936,583 @@ async fn get_album_by_id(
     let row = sqlx::query(query)
         .bind(album_id)
         .fetch_optional(&app_state.db_pool)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let album = row.map(|row| Album {
-        id: row.get("id"),
-        title: row.get("title"),
-        artist: row.get("artist"),
-        year: row.get("year"),
-        genre: row.get("genre"),
-        cover_art_base64: row.get("cover_art_base64"),
-        song_count: row.get("song_count"),
-        total_duration: row.get("total_duration"),
-        folder_path: row.get("folder_path"),
-    });
+        .await?;
+
+    Ok(row.map(|row| Album::from_row(&row, cover_size)))
+}
+
+/// Artists with the number of albums and tracks they appear on, for
+/// `Artist::from_row`. Albums count whether the artist is credited with the
+/// whole album or only with some of its tracks.
+const ARTIST_QUERY: &str = r#"
+    SELECT
+        artists.id, artists.name, artists.sort_name,
+        (
+            SELECT COUNT(*) FROM (
+                SELECT album_id FROM album_artists WHERE artist_id = artists.id
+                UNION
+                SELECT songs.album_id FROM song_artists
+                JOIN songs ON songs.id = song_artists.song_id
+                WHERE song_artists.artist_id = artists.id
+            )
+        ) AS album_count,
+        (SELECT COUNT(*) FROM song_artists WHERE artist_id = artists.id) AS track_count
+    FROM artists
+"#;
+
+/// Get artists in sort name order
+#[tauri::command]
+async fn get_artists(
+    app_state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> AppResult<Vec<Artist>> {
+    let query = format!(
+        "{} ORDER BY artists.sort_name COLLATE NOCASE LIMIT ? OFFSET ?",
+        ARTIST_QUERY
+    );
+
+    let rows = sqlx::query(&query)
+        .bind(limit.unwrap_or(50))
+        .bind(offset.unwrap_or(0))
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+    Ok(rows.iter().map(Artist::from_row).collect())
+}
+
+/// Get a specific artist by ID
+#[tauri::command]
+async fn get_artist_by_id(
+    artist_id: i64,
+    app_state: State<'_, AppState>,
+) -> AppResult<Option<Artist>> {
+    let query = format!("{} WHERE artists.id = ?", ARTIST_QUERY);
+
+    let row = sqlx::query(&query)
+        .bind(artist_id)
+        .fetch_optional(&app_state.db_pool)
+        .await?;
+
+    Ok(row.as_ref().map(Artist::from_row))
+}
+
+/// Albums an artist is credited with, or appears on as a track artist
+#[tauri::command]
+async fn get_artist_albums(
+    artist_id: i64,
+    app_state: State<'_, AppState>,
+    cover_size: Option<u32>,
+) -> AppResult<Vec<Album>> {
+    let query = r#"
+        SELECT
+            id, title, artist, year, genre, cover_id,
+            song_count, total_duration, folder_path
+        FROM albums
+        WHERE id IN (SELECT album_id FROM album_artists WHERE artist_id = ?1)
+            OR id IN (
+                SELECT songs.album_id FROM song_artists
+                JOIN songs ON songs.id = song_artists.song_id
+                WHERE song_artists.artist_id = ?1
+            )
+        ORDER BY year, title
+    "#;
+
+    let rows = sqlx::query(query)
+        .bind(artist_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Album::from_row(row, cover_size))
+        .collect())
+}
+
+/// Songs an artist performs on, grouped by album
+#[tauri::command]
+async fn get_artist_tracks(
+    artist_id: i64,
+    app_state: State<'_, AppState>,
+) -> AppResult<Vec<SongInfo>> {
+    let query = r#"
+        SELECT
+            songs.id, songs.album_id, songs.title, songs.artist, songs.album, songs.genre,
+            songs.duration, songs.path, songs.lyrics_path, songs.album_artist, songs.year,
+            songs.label, songs.track_number, songs.format, songs.codec,
+            songs.replaygain_track_gain, songs.replaygain_track_peak,
+            songs.replaygain_album_gain, songs.replaygain_album_peak
+        FROM songs
+        JOIN song_artists ON song_artists.song_id = songs.id
+        WHERE song_artists.artist_id = ?
+        ORDER BY songs.year, songs.album, songs.track_number, songs.title
+    "#;
+
+    let rows = sqlx::query(query)
+        .bind(artist_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+    Ok(rows.iter().map(SongInfo::from_row).collect())
+}
+
+/// Pairs of each genre with itself and every genre under it, at any depth,
+/// as `subgenres(genre_id, subgenre_id)`
+const SUBGENRES: &str = r#"
+    WITH RECURSIVE subgenres(genre_id, subgenre_id) AS (
+        SELECT id, id FROM genres
+        UNION
+        SELECT subgenres.genre_id, genres.id
+        FROM genres JOIN subgenres ON genres.parent_id = subgenres.subgenre_id
+    )
+"#;
+
+/// Get all genres in name order. Counts cover each genre's subgenres too, and
+/// a song in several of them counts once.
+#[tauri::command]
+async fn get_genres(app_state: State<'_, AppState>) -> AppResult<Vec<Genre>> {
+    let query = format!(
+        r#"
+        {}
+        SELECT
+            genres.id, genres.name, genres.parent_id,
+            COUNT(DISTINCT songs.album_id) AS album_count,
+            COUNT(DISTINCT songs.id) AS track_count,
+            COALESCE(SUM(songs.duration), 0.0) AS total_duration
+        FROM genres
+        LEFT JOIN (
+            SELECT DISTINCT subgenres.genre_id, song_genres.song_id
+            FROM subgenres
+            JOIN song_genres ON song_genres.genre_id = subgenres.subgenre_id
+        ) AS genre_songs ON genre_songs.genre_id = genres.id
+        LEFT JOIN songs ON songs.id = genre_songs.song_id
+        GROUP BY genres.id
+        ORDER BY genres.name
+        "#,
+        SUBGENRES
+    );
+
+    let rows = sqlx::query(&query).fetch_all(&app_state.db_pool).await?;
+
+    Ok(rows.iter().map(Genre::from_row).collect())
+}
+
+/// Albums with songs in a genre or any of its subgenres
+#[tauri::command]
+async fn get_genre_albums(
+    genre_id: i64,
+    app_state: State<'_, AppState>,
+    cover_size: Option<u32>,
+) -> AppResult<Vec<Album>> {
+    let query = format!(
+        r#"
+        {}
+        SELECT
+            id, title, artist, year, genre, cover_id,
+            song_count, total_duration, folder_path
+        FROM albums
+        WHERE id IN (
+            SELECT songs.album_id FROM songs
+            JOIN song_genres ON song_genres.song_id = songs.id
+            JOIN subgenres ON subgenres.subgenre_id = song_genres.genre_id
+            WHERE subgenres.genre_id = ?
+        )
+        ORDER BY artist, year, title
+        "#,
+        SUBGENRES
+    );
+
+    let rows = sqlx::query(&query)
+        .bind(genre_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Album::from_row(row, cover_size))
+        .collect())
+}
+
+/// Add a genre to file others under, returning its id
+#[tauri::command]
+async fn add_genre(name: String, app_state: State<'_, AppState>) -> AppResult<i64> {
+    genres::add_genre(&app_state.db_pool, &name).await
+}
+
+/// File a genre under a broader one, or move it back to the top level
+#[tauri::command]
+async fn set_genre_parent(
+    genre_id: i64,
+    parent_id: Option<i64>,
+    app_state: State<'_, AppState>,
+) -> AppResult<()> {
+    genres::set_parent(&app_state.db_pool, genre_id, parent_id).await
+}
+
+/// Start playing a song from the library. Songs that aren't queued yet are
+/// inserted right after the current track.
+#[tauri::command]
+async fn play_song(song_id: i64, app_state: State<'_, AppState>) -> AppResult<()> {
+    let song = fetch_songs_by_id(&app_state.db_pool, &[song_id])
+        .await?
+        .pop()
+        .ok_or_else(|| AppError::NotFound(format!("Song {}", song_id)))?;
+
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    let index = match queue.position_of(song_id) {
+        Some(index) => index,
+        None => queue.insert_next(song),
+    };
+    queue.jump(index);
+    player.commit_queue(&queue).await?;
+
+    player.send(PlayerCommand::PlayCurrent {
+        start_at: 0.0,
+        paused: false,
+    })
+}
+
+/// Pause playback
+#[tauri::command]
+async fn pause(app_state: State<'_, AppState>) -> AppResult<()> {
+    app_state.player.send(PlayerCommand::Pause)
+}
+
+/// Resume paused playback
+#[tauri::command]
+async fn resume(app_state: State<'_, AppState>) -> AppResult<()> {
+    app_state.player.send(PlayerCommand::Resume)
+}
+
+/// Stop playback
+#[tauri::command]
+async fn stop(app_state: State<'_, AppState>) -> AppResult<()> {
+    app_state.player.send(PlayerCommand::Stop)
+}
+
+/// Seek within the current song
+#[tauri::command]
+async fn seek(seconds: f32, app_state: State<'_, AppState>) -> AppResult<()> {
+    if !seconds.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Seek position must be a number of seconds, got {}",
+            seconds
+        )));
+    }
+    app_state.player.send(PlayerCommand::Seek(seconds))
+}
+
+/// Set the playback volume (0.0 - 1.0)
+#[tauri::command]
+async fn set_volume(volume: f32, app_state: State<'_, AppState>) -> AppResult<()> {
+    app_state.player.send(PlayerCommand::SetVolume(volume))
+}
+
+/// Get current playback status
+#[tauri::command]
+async fn get_player_status(app_state: State<'_, AppState>) -> AppResult<PlayerStatus> {
+    Ok(app_state.player.status().await)
+}
+
+/// Get the play queue in play order
+#[tauri::command]
+async fn get_queue(app_state: State<'_, AppState>) -> AppResult<QueueSnapshot> {
+    Ok(app_state.player.queue().lock().await.snapshot())
+}
+
+/// Append every song of an album to the queue
+#[tauri::command]
+async fn enqueue_album(album_id: i64, app_state: State<'_, AppState>) -> AppResult<()> {
+    let query = r#"
+        SELECT 
+            id, album_id, title, artist, album, genre, duration, path, 
+            lyrics_path, album_artist, year, label, track_number, format, codec,
+            replaygain_track_gain, replaygain_track_peak,
+            replaygain_album_gain, replaygain_album_peak
+        FROM songs 
+        WHERE album_id = ? 
+        ORDER BY CAST(track_number AS INTEGER), title
+    "#;
+
+    let rows = sqlx::query(query)
+        .bind(album_id)
+        .fetch_all(&app_state.db_pool)
+        .await?;
+
+    enqueue(
+        &app_state.player,
+        rows.iter().map(SongInfo::from_row).collect(),
+    )
+    .await
+}
+
+/// Append individual songs to the queue
+#[tauri::command]
+async fn enqueue_songs(song_ids: Vec<i64>, app_state: State<'_, AppState>) -> AppResult<()> {
+    let songs = fetch_songs_by_id(&app_state.db_pool, &song_ids).await?;
+
+    enqueue(&app_state.player, songs).await
+}
+
+/// Move a queue entry from one index to another
+#[tauri::command]
+async fn move_queue_entry(from: usize, to: usize, app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    queue.move_entry(from, to);
+    player.commit_queue(&queue).await
+}
+
+/// Remove an entry from the queue. Removing the current track moves playback on.
+#[tauri::command]
+async fn remove_from_queue(index: usize, app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    let removed_current = queue.remove(index);
+    player.commit_queue(&queue).await?;
+
+    if !removed_current {
+        return Ok(());
+    }
+
+    match player.status().await.state {
+        PlaybackState::Stopped => Ok(()),
+        state => player.send(PlayerCommand::PlayCurrent {
+            start_at: 0.0,
+            paused: state == PlaybackState::Paused,
+        }),
+    }
+}
+
+/// Remove everything from the queue and stop playback
+#[tauri::command]
+async fn clear_queue(app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    queue.clear();
+    player.commit_queue(&queue).await?;
+    player.send(PlayerCommand::Stop)
+}
+
+/// Turn shuffle on or off. Turning it off restores the original queue order.
+#[tauri::command]
+async fn set_shuffle(enabled: bool, app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    queue.set_shuffle(enabled);
+    player.commit_queue(&queue).await
+}
+
+/// Set the repeat mode ("off", "one" or "all")
+#[tauri::command]
+async fn set_repeat_mode(mode: RepeatMode, app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    queue.set_repeat_mode(mode);
+    player.commit_queue(&queue).await
+}
+
+/// Start playing the queue entry at `index`
+#[tauri::command]
+async fn play_queue_index(index: usize, app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    if queue.jump(index).is_none() {
+        return Err(AppError::InvalidInput(format!(
+            "Queue index {} out of range",
+            index
+        )));
+    }
+    player.commit_queue(&queue).await?;
+
+    player.send(PlayerCommand::PlayCurrent {
+        start_at: 0.0,
+        paused: false,
+    })
+}
+
+/// Skip to the next track in the queue
+#[tauri::command]
+async fn next_track(app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    let mut queue = player.queue().lock().await;
+    let has_next = queue.skip_forward().is_some();
+    player.commit_queue(&queue).await?;
+
+    if has_next {
+        player.send(PlayerCommand::PlayCurrent {
+            start_at: 0.0,
+            paused: false,
+        })
+    } else {
+        player.send(PlayerCommand::Stop)
+    }
+}
+
+/// Go back to the previous track, or restart the current one if it has
+/// been playing for more than a few seconds
+#[tauri::command]
+async fn previous_track(app_state: State<'_, AppState>) -> AppResult<()> {
+    let player = &app_state.player;
+    if player.status().await.position > 3.0 {
+        return player.send(PlayerCommand::Seek(0.0));
+    }
+
+    let mut queue = player.queue().lock().await;
+    queue.skip_back();
+    player.commit_queue(&queue).await?;
+
+    player.send(PlayerCommand::PlayCurrent {
+        start_at: 0.0,
+        paused: false,
+    })
+}
+
+/// Append songs to the queue, making the first one current if nothing is
+async fn enqueue(player: &Player, songs: Vec<SongInfo>) -> AppResult<()> {
+    let mut queue = player.queue().lock().await;
+    let first_new = queue.len();
+    queue.enqueue(songs);
+    if queue.current_index().is_none() {
+        queue.jump(first_new);
+    }
+    player.commit_queue(&queue).await
+}
+
+/// Pair items loaded for search hits with the hits' snippets. The items are
+/// in hit order, but hits whose item was deleted meanwhile have none.
+fn with_snippets<T>(
+    items: Vec<T>,
+    hits: Vec<(i64, Vec<SnippetPart>)>,
+    id: impl Fn(&T) -> i64,
+) -> Vec<SearchHit<T>> {
+    let mut snippets: HashMap<i64, Vec<SnippetPart>> = hits.into_iter().collect();
+    items
+        .into_iter()
+        .map(|item| SearchHit {
+            snippet: snippets.remove(&id(&item)).unwrap_or_default(),
+            item,
+        })
+        .collect()
+}
+
+/// Load albums by id, keeping the order of `album_ids`
+async fn fetch_albums_by_id(
+    db_pool: &SqlitePool,
+    album_ids: &[i64],
+    cover_size: Option<u32>,
+) -> Result<Vec<Album>, sqlx::Error> {
+    if album_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; album_ids.len()].join(", ");
+    let query = format!(
+        r#"
+        SELECT
+            id, title, artist, year, genre, cover_id,
+            song_count, total_duration, folder_path
+        FROM albums
+        WHERE id IN ({})
+        "#,
+        placeholders
+    );
+
+    let mut query = sqlx::query(&query);
+    for album_id in album_ids {
+        query = query.bind(album_id);
+    }
+
+    let albums: HashMap<i64, Album> = query
+        .fetch_all(db_pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let album = Album::from_row(row, cover_size);
+            (album.id, album)
+        })
+        .collect();
+
+    Ok(album_ids
+        .iter()
+        .filter_map(|album_id| albums.get(album_id).cloned())
+        .collect())
+}
+
+/// Load artists by id, keeping the order of `artist_ids`
+async fn fetch_artists_by_id(
+    db_pool: &SqlitePool,
+    artist_ids: &[i64],
+) -> Result<Vec<Artist>, sqlx::Error> {
+    if artist_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; artist_ids.len()].join(", ");
+    let query = format!("{} WHERE artists.id IN ({})", ARTIST_QUERY, placeholders);
+
+    let mut query = sqlx::query(&query);
+    for artist_id in artist_ids {
+        query = query.bind(artist_id);
+    }
+
+    let artists: HashMap<i64, Artist> = query
+        .fetch_all(db_pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let artist = Artist::from_row(row);
+            (artist.id, artist)
+        })
+        .collect();
+
+    Ok(artist_ids
+        .iter()
+        .filter_map(|artist_id| artists.get(artist_id).cloned())
+        .collect())
+}
+
+/// Load songs by id, keeping the order of `song_ids`
+async fn fetch_songs_by_id(
+    db_pool: &SqlitePool,
+    song_ids: &[i64],
+) -> Result<Vec<SongInfo>, sqlx::Error> {
+    if song_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; song_ids.len()].join(", ");
+    let query = format!(
+        r#"
+        SELECT 
+            id, album_id, title, artist, album, genre, duration, path, 
+            lyrics_path, album_artist, year, label, track_number, format, codec,
+            replaygain_track_gain, replaygain_track_peak,
+            replaygain_album_gain, replaygain_album_peak
+        FROM songs 
+        WHERE id IN ({})
+        "#,
+        placeholders
+    );
+
+    let mut query = sqlx::query(&query);
+    for song_id in song_ids {
+        query = query.bind(song_id);
+    }
+
+    let songs: HashMap<i64, SongInfo> = query
+        .fetch_all(db_pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let song = SongInfo::from_row(row);
+            (song.id.unwrap_or_default(), song)
+        })
+        .collect();
 
-    Ok(album)
+    Ok(song_ids
+        .iter()
+        .filter_map(|song_id| songs.get(song_id).cloned())
+        .collect())
 }
@@ -0,0 +1,107 @@
+use crate::metadata::scanner::replace_links;
+use sqlx::{Row, SqliteConnection};
+use std::collections::HashMap;
+
+/// Leading articles moved to the end of sort names, so "The Beatles" sorts as
+/// "Beatles, The". Must match the backfill in `0004_artists.sql`.
+const ARTICLES: [&str; 3] = ["The", "An", "A"];
+
+/// Name to sort an artist by
+pub fn sort_name(name: &str) -> String {
+    for article in ARTICLES {
+        let split = article.len() + 1;
+        let is_prefix = name
+            .get(..split)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&format!("{} ", article)));
+        if is_prefix {
+            return format!("{}, {}", &name[split..], &name[..article.len()]);
+        }
+    }
+    name.to_string()
+}
+
+/// Looks up artist ids by name, adding artists that are new. Names already
+/// looked up are remembered, so keep one around for a whole write.
+#[derive(Default)]
+pub struct ArtistIds {
+    ids: HashMap<String, i64>,
+}
+
+impl ArtistIds {
+    pub async fn get(
+        &mut self,
+        conn: &mut SqliteConnection,
+        name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        if let Some(id) = self.ids.get(name) {
+            return Ok(*id);
+        }
+
+        sqlx::query(
+            "INSERT INTO artists (name, sort_name) VALUES (?, ?) ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(name)
+        .bind(sort_name(name))
+        .execute(&mut *conn)
+        .await?;
+        // Names match case-insensitively, so this may find an existing spelling
+        let id = sqlx::query("SELECT id FROM artists WHERE name = ?")
+            .bind(name)
+            .fetch_one(&mut *conn)
+            .await?
+            .get("id");
+
+        self.ids.insert(name.to_string(), id);
+        Ok(id)
+    }
+}
+
+/// Link songs to the artists named for them, replacing their old links
+pub async fn link_songs(
+    conn: &mut SqliteConnection,
+    artist_ids: &mut ArtistIds,
+    songs: &[(i64, Vec<String>)],
+) -> Result<(), sqlx::Error> {
+    let links = resolve(conn, artist_ids, songs).await?;
+    replace_links(conn, "song_artists", "song_id", "artist_id", &links).await
+}
+
+/// Link albums to the artists named for them, replacing their old links
+pub async fn link_albums(
+    conn: &mut SqliteConnection,
+    artist_ids: &mut ArtistIds,
+    albums: &[(i64, Vec<String>)],
+) -> Result<(), sqlx::Error> {
+    let links = resolve(conn, artist_ids, albums).await?;
+    replace_links(conn, "album_artists", "album_id", "artist_id", &links).await
+}
+
+async fn resolve(
+    conn: &mut SqliteConnection,
+    artist_ids: &mut ArtistIds,
+    owners: &[(i64, Vec<String>)],
+) -> Result<Vec<(i64, Vec<i64>)>, sqlx::Error> {
+    let mut links = Vec::with_capacity(owners.len());
+    for (owner_id, names) in owners {
+        let mut ids = Vec::with_capacity(names.len());
+        for name in names {
+            ids.push(artist_ids.get(&mut *conn, name).await?);
+        }
+        links.push((*owner_id, ids));
+    }
+    Ok(links)
+}
+
+/// Delete artists no song or album credits any more
+pub async fn prune_artists(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        DELETE FROM artists
+        WHERE id NOT IN (SELECT artist_id FROM song_artists)
+            AND id NOT IN (SELECT artist_id FROM album_artists)
+        "#,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use crate::metadata::covers::{CachedCover, CoverCache};
+use lofty::picture::{Picture, PictureType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Image extensions looked at in album folders
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+
+/// Subfolders of an album folder that hold its artwork, compared case-insensitively
+const ARTWORK_FOLDERS: [&str; 2] = ["scans", "artwork"];
+
+/// What a picture of an album shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtworkKind {
+    Front,
+    Back,
+    Booklet,
+    Artist,
+    Other,
+}
+
+impl ArtworkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtworkKind::Front => "front",
+            ArtworkKind::Back => "back",
+            ArtworkKind::Booklet => "booklet",
+            ArtworkKind::Artist => "artist",
+            ArtworkKind::Other => "other",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "front" => ArtworkKind::Front,
+            "back" => ArtworkKind::Back,
+            "booklet" => ArtworkKind::Booklet,
+            "artist" => ArtworkKind::Artist,
+            _ => ArtworkKind::Other,
+        }
+    }
+
+    /// Kind of an embedded picture, `None` for ones that say nothing useful
+    fn from_picture_type(picture_type: PictureType) -> Option<Self> {
+        match picture_type {
+            PictureType::CoverFront => Some(ArtworkKind::Front),
+            PictureType::CoverBack => Some(ArtworkKind::Back),
+            PictureType::Leaflet => Some(ArtworkKind::Booklet),
+            PictureType::LeadArtist | PictureType::Artist | PictureType::Band => {
+                Some(ArtworkKind::Artist)
+            }
+            _ => None,
+        }
+    }
+
+    /// Kind of an image file from its lowercase name without extension, such
+    /// as "cover", "Front (2)", "AlbumArt_{...}_Large", "back" or the album
+    /// title. `None` for names that don't look like artwork.
+    fn from_file_stem(stem: &str, album: &str) -> Option<Self> {
+        if stem == album {
+            return Some(ArtworkKind::Front);
+        }
+        if stem.contains("back") {
+            return Some(ArtworkKind::Back);
+        }
+        if ["booklet", "inlay", "inside", "leaflet"]
+            .iter()
+            .any(|word| stem.contains(word))
+        {
+            return Some(ArtworkKind::Booklet);
+        }
+        if stem.contains("artist") || stem.contains("band") {
+            return Some(ArtworkKind::Artist);
+        }
+        if stem == "folder"
+            || stem == "album"
+            || ["cover", "front", "albumart"]
+                .iter()
+                .any(|prefix| stem.starts_with(prefix))
+        {
+            return Some(ArtworkKind::Front);
+        }
+        None
+    }
+}
+
+/// The artwork found for one song
+#[derive(Default)]
+pub struct SongArtwork {
+    /// The best front cover, or failing that the largest embedded picture
+    pub cover: Option<CachedCover>,
+    /// Back covers, booklet scans, artist photos and the like
+    pub secondary: Vec<(ArtworkKind, CachedCover)>,
+}
+
+/// An image file in an album folder
+struct FolderImage {
+    path: PathBuf,
+    /// Lowercase file name without extension
+    stem: String,
+    /// Whether it sits in one of `ARTWORK_FOLDERS`
+    in_artwork_folder: bool,
+    /// Read and measured the first time a song needs it
+    described: OnceLock<Option<CachedCover>>,
+    /// Stored in the cache the first time a song picks it
+    stored: OnceLock<Option<CachedCover>>,
+}
+
+impl FolderImage {
+    fn described(&self) -> Option<CachedCover> {
+        self.described
+            .get_or_init(|| match fs::read(&self.path) {
+                Ok(bytes) => CachedCover::describe(&bytes),
+                Err(e) => {
+                    eprintln!("Couldn't read artwork {}: {}", self.path.display(), e);
+                    None
+                }
+            })
+            .clone()
+    }
+
+    fn stored(&self, covers: &CoverCache) -> Option<CachedCover> {
+        self.stored
+            .get_or_init(|| {
+                let stored = fs::read(&self.path).and_then(|bytes| covers.store(&bytes));
+                stored.unwrap_or_else(|e| {
+                    eprintln!("Couldn't store artwork {}: {}", self.path.display(), e);
+                    None
+                })
+            })
+            .clone()
+    }
+
+    fn kind(&self, album: &str) -> Option<ArtworkKind> {
+        match ArtworkKind::from_file_stem(&self.stem, album) {
+            Some(kind) => Some(kind),
+            None if self.in_artwork_folder => Some(ArtworkKind::Other),
+            None => None,
+        }
+    }
+}
+
+/// Where the bytes of an image a song could use come from
+#[derive(Clone, Copy)]
+enum ImageSource<'a> {
+    Embedded(&'a [u8]),
+    Folder(&'a FolderImage),
+}
+
+/// Finds the artwork of songs, from their tags and the images around them.
+/// Each folder is listed, and each image in it read, once per scan however
+/// many songs share it.
+#[derive(Clone)]
+pub struct ArtworkFinder {
+    covers: CoverCache,
+    folders: Arc<Mutex<HashMap<PathBuf, Arc<Vec<FolderImage>>>>>,
+}
+
+impl ArtworkFinder {
+    pub fn new(covers: &CoverCache) -> Self {
+        ArtworkFinder {
+            covers: covers.clone(),
+            folders: Arc::default(),
+        }
+    }
+
+    /// Pick the cover and secondary artwork of the song at `path` on `album`,
+    /// from its embedded `pictures` and the images in its folder. The largest
+    /// front cover wins. Only the picked images are stored. This blocks.
+    pub fn find(&self, path: &Path, album: &str, pictures: &[Picture]) -> SongArtwork {
+        let album = album.to_lowercase();
+        let mut fronts = Vec::new();
+        let mut embedded_others = Vec::new();
+        let mut secondary = Vec::new();
+
+        for picture in pictures {
+            let Some(cached) = CachedCover::describe(picture.data()) else {
+                continue;
+            };
+            let source = ImageSource::Embedded(picture.data());
+            match ArtworkKind::from_picture_type(picture.pic_type()) {
+                Some(ArtworkKind::Front) => fronts.push((cached, source)),
+                Some(kind) => {
+                    embedded_others.push((cached.clone(), source));
+                    secondary.push((kind, cached, source));
+                }
+                None => embedded_others.push((cached, source)),
+            }
+        }
+
+        let folder_images = path.parent().map(|folder| self.folder_images(folder));
+        for image in folder_images.iter().flat_map(|images| images.iter()) {
+            let Some(kind) = image.kind(&album) else {
+                continue;
+            };
+            let Some(cached) = image.described() else {
+                continue;
+            };
+            let source = ImageSource::Folder(image);
+            match kind {
+                ArtworkKind::Front => fronts.push((cached, source)),
+                kind => secondary.push((kind, cached, source)),
+            }
+        }
+
+        // Any embedded picture beats no cover at all
+        let cover = fronts
+            .into_iter()
+            .max_by_key(|(cached, _)| cached.area())
+            .or_else(|| {
+                embedded_others
+                    .into_iter()
+                    .max_by_key(|(cached, _)| cached.area())
+            });
+
+        if let Some((cover, _)) = &cover {
+            secondary.retain(|(_, cached, _)| cached.hash != cover.hash);
+        }
+        secondary.sort_by(|a, b| a.1.hash.cmp(&b.1.hash));
+        secondary.dedup_by(|a, b| a.1.hash == b.1.hash);
+
+        SongArtwork {
+            cover: cover.and_then(|(cached, source)| self.store(path, cached, source)),
+            secondary: secondary
+                .into_iter()
+                .filter_map(|(kind, cached, source)| {
+                    Some((kind, self.store(path, cached, source)?))
+                })
+                .collect(),
+        }
+    }
+
+    /// Write a picked image of the song at `path` to the cache, `None` if
+    /// that fails
+    fn store(&self, path: &Path, cached: CachedCover, source: ImageSource) -> Option<CachedCover> {
+        match source {
+            ImageSource::Embedded(bytes) => match self.covers.write(&cached, bytes) {
+                Ok(()) => Some(cached),
+                Err(e) => {
+                    eprintln!("Failed to cache artwork of {}: {}", path.display(), e);
+                    None
+                }
+            },
+            ImageSource::Folder(image) => image.stored(&self.covers),
+        }
+    }
+
+    /// The images in `folder` and its artwork subfolders, listed on first use
+    fn folder_images(&self, folder: &Path) -> Arc<Vec<FolderImage>> {
+        if let Some(images) = self.folders.lock().unwrap().get(folder) {
+            return images.clone();
+        }
+
+        let mut images = Vec::new();
+        list_images(folder, false, &mut images);
+        if let Ok(entries) = fs::read_dir(folder) {
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                if ARTWORK_FOLDERS.contains(&name.as_str()) && entry.path().is_dir() {
+                    list_images(&entry.path(), true, &mut images);
+                }
+            }
+        }
+
+        // Another worker may have listed it meanwhile; either list will do
+        let images = Arc::new(images);
+        self.folders
+            .lock()
+            .unwrap()
+            .insert(folder.to_path_buf(), images.clone());
+        images
+    }
+}
+
+/// Add the image files directly inside `folder` to `images`
+fn list_images(folder: &Path, in_artwork_folder: bool, images: &mut Vec<FolderImage>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let is_image = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()));
+        if !is_image || !path.is_file() {
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        images.push(FolderImage {
+            path,
+            stem,
+            in_artwork_folder,
+            described: OnceLock::new(),
+            stored: OnceLock::new(),
+        });
+    }
+}
@@ -0,0 +1,337 @@
+use crate::error::AppError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::ImageReader;
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+/// Edge lengths of the square boxes thumbnails are scaled to fit
+pub const THUMBNAIL_SIZES: [u32; 3] = [64, 256, 512];
+
+const THUMBNAIL_QUALITY: u8 = 85;
+
+/// Cover images on disk, each named by the SHA-256 of its bytes so albums
+/// sharing a picture share one file
+#[derive(Clone, Debug)]
+pub struct CoverCache {
+    dir: PathBuf,
+}
+
+/// An image stored in the cache, or a candidate for it
+#[derive(Clone, Debug)]
+pub struct CachedCover {
+    pub hash: String,
+    pub mime_type: &'static str,
+    pub size: i64,
+    /// Pixel dimensions, when the image header could be read
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl CachedCover {
+    /// Identify and measure an image without storing it. Returns `None` for
+    /// bytes that aren't an image we can show.
+    pub fn describe(bytes: &[u8]) -> Option<Self> {
+        let mime_type = sniff_image_type(bytes)?;
+        let dimensions = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok());
+
+        Some(CachedCover {
+            hash: format!("{:x}", Sha256::digest(bytes)),
+            mime_type,
+            size: bytes.len() as i64,
+            dimensions,
+        })
+    }
+
+    /// How big the image is, for picking the best of several: pixel count
+    /// first, then file size
+    pub fn area(&self) -> (u64, i64) {
+        let (width, height) = self.dimensions.unwrap_or_default();
+        (width as u64 * height as u64, self.size)
+    }
+}
+
+impl CoverCache {
+    pub fn new(dir: PathBuf) -> Self {
+        CoverCache { dir }
+    }
+
+    /// Where the image with `hash` lives, in a subfolder named after its
+    /// first two characters to keep folders small
+    pub fn path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    /// Write an image to the cache unless it is already there. Returns `None`
+    /// for bytes that aren't an image we can show. This blocks.
+    pub fn store(&self, bytes: &[u8]) -> io::Result<Option<CachedCover>> {
+        let Some(cover) = CachedCover::describe(bytes) else {
+            return Ok(None);
+        };
+        self.write(&cover, bytes)?;
+        Ok(Some(cover))
+    }
+
+    /// Write the `bytes` described by `cover` to the cache unless they are
+    /// already there. This blocks.
+    pub fn write(&self, cover: &CachedCover, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path(&cover.hash);
+        if !path.exists() {
+            write_atomically(&path, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Where the thumbnail of the image with `hash` at one of `THUMBNAIL_SIZES` lives
+    fn thumbnail_path(&self, hash: &str, size: u32) -> PathBuf {
+        self.dir.join(&hash[..2]).join(format!("{}_{}", hash, size))
+    }
+
+    /// JPEG of a cached image scaled down to fit `size` pixels, made on first
+    /// use and kept. Images already that small are only re-encoded. This blocks.
+    pub fn thumbnail(&self, hash: &str, size: u32) -> Result<Vec<u8>, AppError> {
+        let path = self.thumbnail_path(hash, size);
+        if let Ok(bytes) = fs::read(&path) {
+            return Ok(bytes);
+        }
+
+        let mut image = ImageReader::open(self.path(hash))?
+            .with_guessed_format()?
+            .decode()?;
+        if image.width() > size || image.height() > size {
+            image = image.resize(size, size, FilterType::Triangle);
+        }
+
+        let mut bytes = Vec::new();
+        JpegEncoder::new_with_quality(&mut bytes, THUMBNAIL_QUALITY)
+            .encode_image(&image.to_rgb8())?;
+        write_atomically(&path, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// Delete every file, thumbnails included, of images whose hash isn't in
+    /// `keep`, such as covers that lost out to another track's. Only call this
+    /// while nothing else is storing images. This blocks.
+    pub fn remove_unlisted(&self, keep: &HashSet<String>) {
+        let Ok(folders) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in folders.filter_map(Result::ok) {
+            let Ok(files) = fs::read_dir(entry.path()) else {
+                continue;
+            };
+            for file in files.filter_map(Result::ok) {
+                let name = file.file_name().to_string_lossy().into_owned();
+                let hash = name.split(['_', '.']).next().unwrap_or_default();
+                if keep.contains(hash) {
+                    continue;
+                }
+                if let Err(e) = fs::remove_file(file.path()) {
+                    eprintln!("Failed to remove {}: {}", file.path().display(), e);
+                }
+            }
+        }
+    }
+
+    /// Delete the files, thumbnails included, of images no longer in `covers`
+    pub fn remove(&self, hashes: &[String]) {
+        for hash in hashes {
+            let thumbnails = THUMBNAIL_SIZES.map(|size| self.thumbnail_path(hash, size));
+            for path in std::iter::once(self.path(hash)).chain(thumbnails) {
+                if let Err(e) = fs::remove_file(&path) {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        eprintln!("Failed to remove {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The smallest thumbnail size at least `requested` pixels across, or `None`
+/// when only the full image is big enough
+pub fn thumbnail_size(requested: u32) -> Option<u32> {
+    THUMBNAIL_SIZES.into_iter().find(|&size| size >= requested)
+}
+
+/// URL the `cover` protocol serves a cover at, as a thumbnail when `size` is given
+pub fn cover_url(cover_id: i64, size: Option<u32>) -> String {
+    // Windows and Android webviews reach custom protocols over http
+    let base = if cfg!(any(windows, target_os = "android")) {
+        "http://cover.localhost"
+    } else {
+        "cover://localhost"
+    };
+    match size.and_then(thumbnail_size) {
+        Some(size) => format!("{}/{}/{}", base, cover_id, size),
+        None => format!("{}/{}", base, cover_id),
+    }
+}
+
+/// Write a file under a unique temporary name and move it into place, so
+/// readers never see half of it
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension(format!("{:08x}.tmp", rand::random::<u32>()));
+    fs::write(&partial, bytes)?;
+    fs::rename(&partial, path)
+}
+
+/// Content type of an image from its first bytes
+fn sniff_image_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// Id of a cached image's row in `covers`, adding the row if it is new
+pub async fn cover_id(
+    conn: &mut SqliteConnection,
+    cover: &CachedCover,
+) -> Result<i64, sqlx::Error> {
+    let (width, height) = cover.dimensions.unzip();
+    sqlx::query(
+        r#"
+        INSERT INTO covers (hash, mime_type, size, width, height) VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT (hash) DO NOTHING
+        "#,
+    )
+    .bind(&cover.hash)
+    .bind(cover.mime_type)
+    .bind(cover.size)
+    .bind(width)
+    .bind(height)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("SELECT id FROM covers WHERE hash = ?")
+        .bind(&cover.hash)
+        .fetch_one(&mut *conn)
+        .await
+        .map(|row| row.get("id"))
+}
+
+/// Hashes of every image with a row in `covers`
+pub async fn listed_hashes(db_pool: &SqlitePool) -> Result<HashSet<String>, sqlx::Error> {
+    let rows = sqlx::query("SELECT hash FROM covers")
+        .fetch_all(db_pool)
+        .await?;
+    Ok(rows.iter().map(|row| row.get("hash")).collect())
+}
+
+/// Delete the rows of images no album uses any more, as its cover or other
+/// artwork. Returns their hashes so the files can go once the deletion is
+/// committed.
+pub async fn prune_covers(conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        DELETE FROM covers
+        WHERE id NOT IN (SELECT cover_id FROM albums WHERE cover_id IS NOT NULL)
+            AND id NOT IN (SELECT cover_id FROM album_artwork)
+        RETURNING hash
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows.iter().map(|row| row.get("hash")).collect())
+}
+
+/// Bytes and content type of the cover with the given id, or of its
+/// thumbnail at one of `THUMBNAIL_SIZES`
+pub async fn load_cover(
+    db_pool: &SqlitePool,
+    cache: &CoverCache,
+    cover_id: i64,
+    size: Option<u32>,
+) -> Result<(Vec<u8>, String), AppError> {
+    let row = sqlx::query("SELECT hash, mime_type FROM covers WHERE id = ?")
+        .bind(cover_id)
+        .fetch_optional(db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Cover {}", cover_id)))?;
+    let hash: String = row.get("hash");
+
+    match size {
+        Some(size) if THUMBNAIL_SIZES.contains(&size) => {
+            let cache = cache.clone();
+            let bytes = tokio::task::spawn_blocking(move || cache.thumbnail(&hash, size)).await??;
+            Ok((bytes, "image/jpeg".to_string()))
+        }
+        Some(size) => Err(AppError::InvalidInput(format!("No {} px thumbnails", size))),
+        None => {
+            let bytes = tokio::fs::read(cache.path(&hash)).await?;
+            Ok((bytes, row.get("mime_type")))
+        }
+    }
+}
+
+/// Releases before the cache kept covers as base64 in `albums`. Move them into
+/// the cache and drop the old column. Safe to repeat if it was interrupted.
+pub async fn move_inline_covers(db_pool: &SqlitePool, cache: &CoverCache) -> Result<(), AppError> {
+    let has_inline_covers =
+        sqlx::query("SELECT 1 FROM pragma_table_info('albums') WHERE name = 'cover_art_base64'")
+            .fetch_optional(db_pool)
+            .await?
+            .is_some();
+    if !has_inline_covers {
+        return Ok(());
+    }
+
+    let album_ids: Vec<i64> =
+        sqlx::query("SELECT id FROM albums WHERE cover_art_base64 IS NOT NULL")
+            .fetch_all(db_pool)
+            .await?
+            .iter()
+            .map(|row| row.get("id"))
+            .collect();
+
+    // One album at a time, so only one image is in memory
+    for album_id in album_ids {
+        let encoded: String = sqlx::query("SELECT cover_art_base64 FROM albums WHERE id = ?")
+            .bind(album_id)
+            .fetch_one(db_pool)
+            .await?
+            .get("cover_art_base64");
+
+        let cached = match STANDARD.decode(encoded.trim()) {
+            Ok(bytes) => cache.store(&bytes)?,
+            Err(e) => {
+                eprintln!("Dropping unreadable cover of album {}: {}", album_id, e);
+                None
+            }
+        };
+
+        let mut conn = db_pool.acquire().await?;
+        let cover_id = match &cached {
+            Some(cover) => Some(cover_id(&mut conn, cover).await?),
+            None => None,
+        };
+        sqlx::query("UPDATE albums SET cover_id = ?, cover_art_base64 = NULL WHERE id = ?")
+            .bind(cover_id)
+            .bind(album_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query("ALTER TABLE albums DROP COLUMN cover_art_base64")
+        .execute(db_pool)
+        .await?;
+
+    Ok(())
+}
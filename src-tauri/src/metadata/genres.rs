@@ -0,0 +1,136 @@
+use crate::error::AppError;
+use crate::metadata::scanner::replace_links;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+
+/// Looks up genre ids by name, adding genres that are new. Names already
+/// looked up are remembered, so keep one around for a whole write.
+#[derive(Default)]
+pub struct GenreIds {
+    ids: HashMap<String, i64>,
+}
+
+impl GenreIds {
+    pub async fn get(
+        &mut self,
+        conn: &mut SqliteConnection,
+        name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        if let Some(id) = self.ids.get(name) {
+            return Ok(*id);
+        }
+
+        sqlx::query("INSERT INTO genres (name) VALUES (?) ON CONFLICT (name) DO NOTHING")
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+        // Names match case-insensitively, so this may find an existing spelling
+        let id = sqlx::query("SELECT id FROM genres WHERE name = ?")
+            .bind(name)
+            .fetch_one(&mut *conn)
+            .await?
+            .get("id");
+
+        self.ids.insert(name.to_string(), id);
+        Ok(id)
+    }
+}
+
+/// Link songs to the genres named for them, replacing their old links
+pub async fn link_songs(
+    conn: &mut SqliteConnection,
+    genre_ids: &mut GenreIds,
+    songs: &[(i64, Vec<String>)],
+) -> Result<(), sqlx::Error> {
+    let mut links = Vec::with_capacity(songs.len());
+    for (song_id, names) in songs {
+        let mut ids = Vec::with_capacity(names.len());
+        for name in names {
+            ids.push(genre_ids.get(&mut *conn, name).await?);
+        }
+        links.push((*song_id, ids));
+    }
+    replace_links(conn, "song_genres", "song_id", "genre_id", &links).await
+}
+
+/// Delete genres no song has any more, unless the user placed them in the
+/// hierarchy
+pub async fn prune_genres(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        DELETE FROM genres
+        WHERE id NOT IN (SELECT genre_id FROM song_genres)
+            AND parent_id IS NULL
+            AND id NOT IN (SELECT parent_id FROM genres WHERE parent_id IS NOT NULL)
+        "#,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Add a genre no song has yet, such as a parent for existing ones. Returns
+/// the id of the genre, which may already exist under another spelling.
+pub async fn add_genre(db_pool: &SqlitePool, name: &str) -> Result<i64, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Genre name is empty".to_string()));
+    }
+
+    let mut conn = db_pool.acquire().await?;
+    Ok(GenreIds::default().get(&mut conn, name).await?)
+}
+
+/// File a genre under `parent_id`, or move it to the top level with `None`
+pub async fn set_parent(
+    db_pool: &SqlitePool,
+    genre_id: i64,
+    parent_id: Option<i64>,
+) -> Result<(), AppError> {
+    let mut tx = db_pool.begin().await?;
+
+    if let Some(parent_id) = parent_id {
+        let parent = sqlx::query("SELECT id FROM genres WHERE id = ?")
+            .bind(parent_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if parent.is_none() {
+            return Err(AppError::NotFound(format!("Genre {}", parent_id)));
+        }
+
+        // The genre itself and everything under it can't become its parent
+        let is_subgenre = sqlx::query(
+            r#"
+            WITH RECURSIVE subgenres(id) AS (
+                SELECT ?1
+                UNION
+                SELECT genres.id FROM genres JOIN subgenres ON genres.parent_id = subgenres.id
+            )
+            SELECT 1 FROM subgenres WHERE id = ?2
+            "#,
+        )
+        .bind(genre_id)
+        .bind(parent_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+        if is_subgenre {
+            return Err(AppError::InvalidInput(
+                "A genre can't be filed under itself or one of its subgenres".to_string(),
+            ));
+        }
+    }
+
+    let updated = sqlx::query("UPDATE genres SET parent_id = ? WHERE id = ?")
+        .bind(parent_id)
+        .bind(genre_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("Genre {}", genre_id)));
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,401 @@
+use crate::error::AppError;
+use crate::player::decoder::TrackDecoder;
+use rodio::Source;
+use sqlx::{Row, SqlitePool};
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Loudness computed gains aim for, the ReplayGain 2.0 reference level
+const REFERENCE_LUFS: f64 = -18.0;
+
+/// Blocks quieter than this never count towards integrated loudness
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Blocks this far below the ungated loudness are dropped by the relative gate
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// True peak is measured on a signal oversampled by this factor
+const OVERSAMPLING: usize = 4;
+
+/// Interpolation filter taps per oversampling phase
+const TAPS_PER_PHASE: usize = 12;
+
+/// Samples decoded per call into the meter
+const CHUNK_SAMPLES: usize = 8192;
+
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// The two-stage K-weighting filter from ITU-R BS.1770, derived for any sample rate
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    // High shelf modelling the acoustic effect of the head
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b0: (vh + vb * k / q + k * k) / a0,
+        b1: 2.0 * (k * k - vh) / a0,
+        b2: (vh - vb * k / q + k * k) / a0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+        ..Default::default()
+    };
+
+    // RLB high pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b0: 1.0,
+        b1: -2.0,
+        b2: 1.0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+        ..Default::default()
+    };
+
+    [shelf, high_pass]
+}
+
+/// Polyphase windowed-sinc filter for `OVERSAMPLING`x interpolation, each phase
+/// normalised to unity gain
+fn interpolation_filter() -> Vec<[f64; TAPS_PER_PHASE]> {
+    let length = OVERSAMPLING * TAPS_PER_PHASE;
+    let center = (length - 1) as f64 / 2.0;
+
+    (0..OVERSAMPLING)
+        .map(|phase| {
+            let mut taps = [0.0; TAPS_PER_PHASE];
+            for (tap, value) in taps.iter_mut().enumerate() {
+                let n = (tap * OVERSAMPLING + phase) as f64;
+                let x = (n - center) / OVERSAMPLING as f64;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let window = 0.5 - 0.5 * (2.0 * PI * (n + 0.5) / length as f64).cos();
+                *value = sinc * window;
+            }
+            let sum: f64 = taps.iter().sum();
+            taps.iter_mut().for_each(|value| *value /= sum);
+            taps
+        })
+        .collect()
+}
+
+/// Measures EBU R128 integrated loudness and true peak of one track
+pub struct LoudnessMeter {
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    interpolation: Vec<[f64; TAPS_PER_PHASE]>,
+    history: Vec<[f64; TAPS_PER_PHASE]>,
+    segment_frames: usize,
+    segment_energy: f64,
+    frames_in_segment: usize,
+    /// Mean weighted power of every complete 100ms segment
+    segments: Vec<f64>,
+    peak: f64,
+    channel: usize,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        // BS.1770 channel weights: surrounds count +1.5 dB and the LFE not at all
+        let weights = (0..channels)
+            .map(|channel| match channel {
+                3 if channels > 3 => 0.0,
+                channel if channel >= 4 => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+
+        LoudnessMeter {
+            channels,
+            weights,
+            filters: vec![k_weighting(sample_rate); channels],
+            interpolation: interpolation_filter(),
+            history: vec![[0.0; TAPS_PER_PHASE]; channels],
+            segment_frames: (sample_rate as usize / 10).max(1),
+            segment_energy: 0.0,
+            frames_in_segment: 0,
+            segments: Vec::new(),
+            peak: 0.0,
+            channel: 0,
+        }
+    }
+
+    /// Feed interleaved samples
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let x = sample as f64;
+            let channel = self.channel;
+
+            let [shelf, high_pass] = &mut self.filters[channel];
+            let weighted = high_pass.process(shelf.process(x));
+            self.segment_energy += self.weights[channel] * weighted * weighted;
+
+            let history = &mut self.history[channel];
+            history.rotate_right(1);
+            history[0] = x;
+            for taps in &self.interpolation {
+                let y: f64 = taps.iter().zip(history.iter()).map(|(t, h)| t * h).sum();
+                self.peak = self.peak.max(y.abs());
+            }
+            self.peak = self.peak.max(x.abs());
+
+            self.channel += 1;
+            if self.channel == self.channels {
+                self.channel = 0;
+                self.frames_in_segment += 1;
+                if self.frames_in_segment == self.segment_frames {
+                    self.segments
+                        .push(self.segment_energy / self.segment_frames as f64);
+                    self.segment_energy = 0.0;
+                    self.frames_in_segment = 0;
+                }
+            }
+        }
+    }
+
+    /// Power of each 400ms gating block, overlapping by 75%
+    fn blocks(&self) -> impl Iterator<Item = f64> + '_ {
+        self.segments
+            .windows(4)
+            .map(|w| w.iter().sum::<f64>() / 4.0)
+    }
+
+    /// Gated integrated loudness in LUFS, or `None` for silence or very short tracks
+    pub fn integrated_loudness(&self) -> Option<f64> {
+        gated_loudness(&self.blocks().collect::<Vec<_>>())
+    }
+
+    /// Highest true peak over all channels, as linear amplitude
+    pub fn true_peak(&self) -> f64 {
+        self.peak
+    }
+}
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Apply the absolute and relative gates to a set of block powers
+fn gated_loudness(blocks: &[f64]) -> Option<f64> {
+    let audible: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|&power| power > 0.0 && block_loudness(power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if audible.is_empty() {
+        return None;
+    }
+
+    let ungated = audible.iter().sum::<f64>() / audible.len() as f64;
+    let threshold = block_loudness(ungated) + RELATIVE_GATE_LU;
+
+    let gated: Vec<f64> = audible
+        .into_iter()
+        .filter(|&power| block_loudness(power) > threshold)
+        .collect();
+    if gated.is_empty() {
+        return None;
+    }
+
+    Some(block_loudness(
+        gated.iter().sum::<f64>() / gated.len() as f64,
+    ))
+}
+
+/// Integrated loudness of several tracks played as one program, as R128 defines album loudness
+pub fn album_loudness(meters: &[LoudnessMeter]) -> Option<f64> {
+    let blocks: Vec<f64> = meters.iter().flat_map(|meter| meter.blocks()).collect();
+    gated_loudness(&blocks)
+}
+
+/// Gain in dB that brings `loudness` to the reference level
+pub fn gain_for(loudness: f64) -> f32 {
+    (REFERENCE_LUFS - loudness) as f32
+}
+
+/// Decode a whole file and measure it
+pub fn analyze_file(path: &Path) -> Result<LoudnessMeter, String> {
+    let mut decoder = TrackDecoder::open(path).map_err(|e| e.to_string())?;
+    let mut meter = LoudnessMeter::new(decoder.channels() as usize, decoder.sample_rate());
+    let mut chunk = Vec::with_capacity(CHUNK_SAMPLES);
+
+    loop {
+        chunk.clear();
+        chunk.extend(decoder.by_ref().take(CHUNK_SAMPLES));
+        if chunk.is_empty() {
+            break;
+        }
+        meter.add_samples(&chunk);
+    }
+
+    Ok(meter)
+}
+
+struct AlbumAnalysis {
+    tracks: Vec<(i64, Option<(f32, f32)>)>,
+    album: Option<(f32, f32)>,
+}
+
+/// Measure every track of an album, plus the album as a whole. The album
+/// value is left out when a track can't be decoded, since it would only
+/// describe part of the album; a later run fills it in once the track reads.
+fn analyze_album(songs: Vec<(i64, PathBuf)>) -> AlbumAnalysis {
+    let mut tracks = Vec::with_capacity(songs.len());
+    let mut meters = Vec::with_capacity(songs.len());
+
+    for (song_id, path) in songs {
+        match analyze_file(&path) {
+            Ok(meter) => {
+                let result = meter
+                    .integrated_loudness()
+                    .map(|loudness| (gain_for(loudness), meter.true_peak() as f32));
+                tracks.push((song_id, result));
+                meters.push(meter);
+            }
+            Err(e) => {
+                eprintln!("Loudness analysis failed for {}: {}", path.display(), e);
+                tracks.push((song_id, None));
+            }
+        }
+    }
+
+    if meters.len() < tracks.len() {
+        return AlbumAnalysis {
+            tracks,
+            album: None,
+        };
+    }
+
+    let album_peak = meters
+        .iter()
+        .map(|meter| meter.true_peak() as f32)
+        .fold(0.0, f32::max);
+    let album = album_loudness(&meters).map(|loudness| (gain_for(loudness), album_peak));
+
+    AlbumAnalysis { tracks, album }
+}
+
+/// Compute ReplayGain values for every album with songs that were never
+/// analysed and lack a track or album gain. Existing values are never
+/// overwritten, and songs are marked as analysed even when analysis fails.
+pub async fn analyze_missing_loudness(
+    db_pool: SqlitePool,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    let album_ids: Vec<i64> = sqlx::query(
+        r#"
+        SELECT DISTINCT a.id
+        FROM albums a
+        JOIN songs s ON s.album_id = a.id
+        WHERE s.loudness_analyzed_at IS NULL
+            AND (s.replaygain_track_gain IS NULL OR a.replaygain_album_gain IS NULL)
+        "#,
+    )
+    .fetch_all(&db_pool)
+    .await?
+    .iter()
+    .map(|row| row.get("id"))
+    .collect();
+
+    let total_albums = album_ids.len() as f32;
+
+    for (index, album_id) in album_ids.into_iter().enumerate() {
+        let progress = (index as f32 / total_albums) * 100.0;
+        let _ = app_handle.emit("loudness_progress", progress);
+
+        let songs: Vec<(i64, PathBuf)> =
+            sqlx::query("SELECT id, path FROM songs WHERE album_id = ?")
+                .bind(album_id)
+                .fetch_all(&db_pool)
+                .await?
+                .iter()
+                .map(|row| (row.get("id"), PathBuf::from(row.get::<String, _>("path"))))
+                .collect();
+
+        let analysis = tokio::task::spawn_blocking(move || analyze_album(songs)).await?;
+
+        for (song_id, result) in analysis.tracks {
+            let (gain, peak) = result.unzip();
+            sqlx::query(
+                r#"
+                UPDATE songs SET
+                    replaygain_track_gain = COALESCE(replaygain_track_gain, ?),
+                    replaygain_track_peak = COALESCE(replaygain_track_peak, ?),
+                    loudness_analyzed_at = CURRENT_TIMESTAMP
+                WHERE id = ?
+                "#,
+            )
+            .bind(gain)
+            .bind(peak)
+            .bind(song_id)
+            .execute(&db_pool)
+            .await?;
+        }
+
+        if let Some((gain, peak)) = analysis.album {
+            sqlx::query(
+                r#"
+                UPDATE albums SET
+                    replaygain_album_gain = COALESCE(replaygain_album_gain, ?),
+                    replaygain_album_peak = COALESCE(replaygain_album_peak, ?)
+                WHERE id = ?
+                "#,
+            )
+            .bind(gain)
+            .bind(peak)
+            .bind(album_id)
+            .execute(&db_pool)
+            .await?;
+
+            sqlx::query(
+                r#"
+                UPDATE songs SET
+                    replaygain_album_gain = COALESCE(replaygain_album_gain, ?),
+                    replaygain_album_peak = COALESCE(replaygain_album_peak, ?)
+                WHERE album_id = ?
+                "#,
+            )
+            .bind(gain)
+            .bind(peak)
+            .bind(album_id)
+            .execute(&db_pool)
+            .await?;
+        }
+    }
+
+    let _ = app_handle.emit("loudness_complete", ());
+
+    Ok(())
+}
@@ -1 +1,11 @@
+pub mod artists;
+pub mod artwork;
+pub mod covers;
+pub mod genres;
+pub mod loudness;
+pub mod query;
+pub mod roots;
 pub mod scanner;
+pub mod search;
+pub mod splitting;
+pub mod watcher;
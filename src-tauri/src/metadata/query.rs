@@ -0,0 +1,856 @@
+use sqlx::{QueryBuilder, Sqlite};
+use std::fmt;
+
+/// Why a query couldn't be parsed, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    pub message: String,
+    /// Character offset of the problem in the query
+    pub position: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Fields matched as text
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextField {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    Genre,
+    Label,
+    Format,
+    Codec,
+    Path,
+    Lyrics,
+}
+
+/// Fields compared as numbers
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumberField {
+    Year,
+    Duration,
+    Track,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Text(TextField),
+    Number(NumberField),
+}
+
+/// Field names, aliases included, in the order error messages list them
+const FIELDS: [(&str, Field); 14] = [
+    ("title", Field::Text(TextField::Title)),
+    ("artist", Field::Text(TextField::Artist)),
+    ("albumartist", Field::Text(TextField::AlbumArtist)),
+    ("album_artist", Field::Text(TextField::AlbumArtist)),
+    ("album", Field::Text(TextField::Album)),
+    ("genre", Field::Text(TextField::Genre)),
+    ("label", Field::Text(TextField::Label)),
+    ("format", Field::Text(TextField::Format)),
+    ("codec", Field::Text(TextField::Codec)),
+    ("path", Field::Text(TextField::Path)),
+    ("lyrics", Field::Text(TextField::Lyrics)),
+    ("year", Field::Number(NumberField::Year)),
+    ("duration", Field::Number(NumberField::Duration)),
+    ("track", Field::Number(NumberField::Track)),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparison {
+    Equal(f64),
+    Less(f64),
+    LessOrEqual(f64),
+    Greater(f64),
+    GreaterOrEqual(f64),
+    /// Inclusive at both ends, open where `None`
+    Between(Option<f64>, Option<f64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Text {
+        field: TextField,
+        value: String,
+        exact: bool,
+    },
+    Number {
+        field: NumberField,
+        comparison: Comparison,
+    },
+    /// Plain text, looked up in the search index. Quoted text is a phrase,
+    /// a bare word also matches as the start of a longer word.
+    FreeText { value: String, quoted: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    negated: bool,
+    condition: Condition,
+}
+
+/// A structured library query such as
+/// `artist:"Radiohead" year:1995..2000 genre:rock duration:>300 -label:XL`.
+///
+/// A query is a list of terms separated by spaces, all of which must match.
+/// A term is `field:value`, or plain text matched against titles, artists,
+/// albums, genres and labels. A leading `-` excludes what the term matches.
+/// Values with spaces go in double quotes, with `\"` for a quote inside.
+///
+/// Text fields match anywhere in the value, ignoring case, or the whole value
+/// with a leading `=` as in `artist:=Low`; `lyrics:` matches whole words.
+/// Number fields take `300`, `=300`, `>300`, `>=300`, `<300`, `<=300` or a
+/// range such as `1995..2000`, `1995..` or `..2000`. Durations may also be
+/// written as minutes, `>5:00`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        Parser::new(input).parse()
+    }
+
+    /// Append ` AND <condition>` for each term to a statement selecting from
+    /// `songs`, binding every value
+    pub fn push_conditions(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        for term in &self.terms {
+            // Missing values count as not matching, so excluding a label
+            // keeps songs that have none
+            query.push(if term.negated {
+                " AND NOT IFNULL(("
+            } else {
+                " AND IFNULL(("
+            });
+            push_condition(query, &term.condition);
+            query.push("), 0)");
+        }
+    }
+}
+
+fn push_condition(query: &mut QueryBuilder<'_, Sqlite>, condition: &Condition) {
+    match condition {
+        Condition::Text {
+            field: TextField::Lyrics,
+            value,
+            ..
+        } => push_index_match(query, "lyrics", value, false),
+        Condition::Text {
+            field,
+            value,
+            exact,
+        } => push_text_condition(query, *field, value, *exact),
+        Condition::Number { field, comparison } => {
+            let column = match field {
+                NumberField::Year => "CAST(substr(songs.year, 1, 4) AS INTEGER)",
+                NumberField::Duration => "songs.duration",
+                NumberField::Track => "CAST(songs.track_number AS INTEGER)",
+            };
+            push_comparison(query, column, comparison);
+        }
+        Condition::FreeText { value, quoted } => {
+            push_index_match(query, "- {lyrics}", value, !quoted)
+        }
+    }
+}
+
+fn push_text_condition(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    field: TextField,
+    value: &str,
+    exact: bool,
+) {
+    // Multi-value fields are matched against each linked name
+    let (prefix, column, suffix) = match field {
+        TextField::Title => ("", "songs.title", ""),
+        TextField::Album => ("", "songs.album", ""),
+        TextField::Label => ("", "songs.label", ""),
+        TextField::Format => ("", "songs.format", ""),
+        TextField::Codec => ("", "songs.codec", ""),
+        TextField::Path => ("", "songs.path", ""),
+        TextField::Artist => (
+            r#"EXISTS (
+                SELECT 1 FROM song_artists
+                JOIN artists ON artists.id = song_artists.artist_id
+                WHERE song_artists.song_id = songs.id AND "#,
+            "artists.name",
+            ")",
+        ),
+        TextField::AlbumArtist => (
+            r#"EXISTS (
+                SELECT 1 FROM album_artists
+                JOIN artists ON artists.id = album_artists.artist_id
+                WHERE album_artists.album_id = songs.album_id AND "#,
+            "artists.name",
+            ")",
+        ),
+        // A genre also matches the songs of its subgenres
+        TextField::Genre => (
+            r#"EXISTS (
+                WITH RECURSIVE matched (id) AS (
+                    SELECT id FROM genres WHERE "#,
+            "genres.name",
+            r#"
+                    UNION
+                    SELECT genres.id FROM genres JOIN matched ON genres.parent_id = matched.id
+                )
+                SELECT 1 FROM song_genres
+                WHERE song_genres.song_id = songs.id
+                    AND song_genres.genre_id IN (SELECT id FROM matched)
+            )"#,
+        ),
+        TextField::Lyrics => unreachable!("lyrics are matched through the search index"),
+    };
+
+    query.push(prefix).push(column);
+    if exact {
+        query
+            .push(" = ")
+            .push_bind(value.to_string())
+            .push(" COLLATE NOCASE");
+    } else {
+        query
+            .push(" LIKE ")
+            .push_bind(format!("%{}%", escape_like(value)))
+            .push(" ESCAPE '\\'");
+    }
+    query.push(suffix);
+}
+
+/// Match songs through the search index, limited to `columns` in FTS5 column
+/// filter syntax
+fn push_index_match(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    columns: &str,
+    value: &str,
+    prefix: bool,
+) {
+    let phrase = format!(
+        "{} : (\"{}\"{})",
+        columns,
+        value.replace('"', "\"\""),
+        if prefix { "*" } else { "" }
+    );
+    query
+        .push("songs.id IN (SELECT rowid FROM song_search WHERE song_search MATCH ")
+        .push_bind(phrase)
+        .push(")");
+}
+
+fn push_comparison(query: &mut QueryBuilder<'_, Sqlite>, column: &str, comparison: &Comparison) {
+    let (operator, value) = match comparison {
+        Comparison::Equal(value) => ("=", value),
+        Comparison::Less(value) => ("<", value),
+        Comparison::LessOrEqual(value) => ("<=", value),
+        Comparison::Greater(value) => (">", value),
+        Comparison::GreaterOrEqual(value) => (">=", value),
+        Comparison::Between(min, max) => {
+            query.push(column).push(" IS NOT NULL");
+            if let Some(min) = min {
+                query
+                    .push(" AND ")
+                    .push(column)
+                    .push(" >= ")
+                    .push_bind(*min);
+            }
+            if let Some(max) = max {
+                query
+                    .push(" AND ")
+                    .push(column)
+                    .push(" <= ")
+                    .push_bind(*max);
+            }
+            return;
+        }
+    };
+    query
+        .push(column)
+        .push(" ")
+        .push(operator)
+        .push(" ")
+        .push_bind(*value);
+}
+
+/// Escape the wildcards of a LIKE pattern, with `\` as the escape character
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// Byte offset of the next character
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser { input, offset: 0 }
+    }
+
+    fn parse(mut self) -> Result<Query, QueryError> {
+        let mut terms = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().is_empty() {
+                return Ok(Query { terms });
+            }
+            terms.push(self.term()?);
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, offset: usize, message: String) -> QueryError {
+        QueryError {
+            message,
+            position: self.input[..offset].chars().count(),
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, QueryError> {
+        let start = self.offset;
+        let negated = self.peek() == Some('-');
+        if negated {
+            self.offset += 1;
+            if self.peek().is_none_or(char::is_whitespace) {
+                return Err(self.error(start, "Nothing to exclude after \"-\"".to_string()));
+            }
+        }
+
+        let name_start = self.offset;
+        let name_len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if name_len == 0 || !self.rest()[name_len..].starts_with(':') {
+            let (value, quoted) = self.value()?;
+            // The search index can't look for nothing
+            if value.trim().is_empty() {
+                return Err(self.error(name_start, "Missing the text between these quotes".into()));
+            }
+            return Ok(Term {
+                negated,
+                condition: Condition::FreeText { value, quoted },
+            });
+        }
+
+        let name = self.rest()[..name_len].to_lowercase();
+        let field = field_named(&name).ok_or_else(|| {
+            let known: Vec<&str> = FIELDS.iter().map(|(name, _)| *name).collect();
+            self.error(
+                name_start,
+                format!(
+                    "Unknown field \"{}\". Known fields are {}",
+                    &self.rest()[..name_len],
+                    known.join(", ")
+                ),
+            )
+        })?;
+        self.offset += name_len + 1;
+
+        let value_start = self.offset;
+        if self.peek().is_none_or(char::is_whitespace) {
+            return Err(self.error(value_start, format!("Missing a value after \"{}:\"", name)));
+        }
+        let condition = match field {
+            Field::Text(field) => {
+                let exact = self.peek() == Some('=');
+                if exact {
+                    self.offset += 1;
+                }
+                let (value, _) = self.value()?;
+                if value.is_empty() {
+                    return Err(
+                        self.error(value_start, format!("Missing a value after \"{}:\"", name))
+                    );
+                }
+                Condition::Text {
+                    field,
+                    value,
+                    exact,
+                }
+            }
+            Field::Number(field) => {
+                let (value, _) = self.value()?;
+                let comparison = parse_comparison(&value, field).map_err(|message| {
+                    self.error(value_start, format!("\"{}:\" {}", name, message))
+                })?;
+                Condition::Number { field, comparison }
+            }
+        };
+
+        Ok(Term { negated, condition })
+    }
+
+    /// A quoted string or a run of characters up to the next space. Returns
+    /// the text and whether it was quoted.
+    fn value(&mut self) -> Result<(String, bool), QueryError> {
+        if self.peek() != Some('"') {
+            let len = self
+                .rest()
+                .find(char::is_whitespace)
+                .unwrap_or(self.rest().len());
+            let value = self.rest()[..len].to_string();
+            self.offset += len;
+            return Ok((value, false));
+        }
+
+        let quote = self.offset;
+        self.offset += 1;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.offset += i + 1;
+                    return Ok((value, true));
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => value.push(escaped),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        Err(self.error(quote, "Missing the closing quote of this value".to_string()))
+    }
+}
+
+fn field_named(name: &str) -> Option<Field> {
+    FIELDS
+        .iter()
+        .find(|(field_name, _)| *field_name == name)
+        .map(|(_, field)| *field)
+}
+
+/// Parse `300`, `>300`, `1995..2000` and the like
+fn parse_comparison(value: &str, field: NumberField) -> Result<Comparison, String> {
+    if let Some((min, max)) = value.split_once("..") {
+        let min = (!min.is_empty())
+            .then(|| parse_number(min, field))
+            .transpose()?;
+        let max = (!max.is_empty())
+            .then(|| parse_number(max, field))
+            .transpose()?;
+        return match (min, max) {
+            (None, None) => Err("needs at least one end of the range".to_string()),
+            (Some(min), Some(max)) if min > max => Err(format!(
+                "has the empty range {}, whose start is after its end",
+                value
+            )),
+            (min, max) => Ok(Comparison::Between(min, max)),
+        };
+    }
+
+    let (operator, comparison): (&str, fn(f64) -> Comparison) = if value.starts_with(">=") {
+        (">=", Comparison::GreaterOrEqual)
+    } else if value.starts_with("<=") {
+        ("<=", Comparison::LessOrEqual)
+    } else if value.starts_with('>') {
+        (">", Comparison::Greater)
+    } else if value.starts_with('<') {
+        ("<", Comparison::Less)
+    } else if value.starts_with('=') {
+        ("=", Comparison::Equal)
+    } else {
+        ("", Comparison::Equal)
+    };
+    parse_number(&value[operator.len()..], field).map(comparison)
+}
+
+/// A number, or for durations also `minutes:seconds`
+fn parse_number(value: &str, field: NumberField) -> Result<f64, String> {
+    let invalid = || match field {
+        NumberField::Duration => {
+            format!("expects seconds or minutes:seconds, found \"{}\"", value)
+        }
+        _ => format!("expects a whole number, found \"{}\"", value),
+    };
+
+    let number = match (field, value.split_once(':')) {
+        (NumberField::Duration, Some((minutes, seconds))) => {
+            let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+            let seconds: f64 = seconds.parse().map_err(|_| invalid())?;
+            if !(0.0..60.0).contains(&seconds) {
+                return Err(invalid());
+            }
+            minutes as f64 * 60.0 + seconds
+        }
+        (NumberField::Duration, None) => value.parse().map_err(|_| invalid())?,
+        _ => value.parse::<u32>().map_err(|_| invalid())? as f64,
+    };
+
+    if number.is_finite() && number >= 0.0 {
+        Ok(number)
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(input: &str) -> Vec<Term> {
+        Query::parse(input).unwrap().terms
+    }
+
+    fn condition(input: &str) -> Condition {
+        let mut terms = terms(input);
+        assert_eq!(terms.len(), 1, "{:?}", terms);
+        terms.remove(0).condition
+    }
+
+    fn comparison(input: &str) -> Comparison {
+        match condition(input) {
+            Condition::Number { comparison, .. } => comparison,
+            other => panic!("not a number condition: {:?}", other),
+        }
+    }
+
+    fn error(input: &str) -> String {
+        Query::parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_a_mixed_query() {
+        assert_eq!(
+            terms(r#"artist:"Radiohead" year:1995..2000 genre:rock duration:>300 -label:XL"#),
+            vec![
+                Term {
+                    negated: false,
+                    condition: Condition::Text {
+                        field: TextField::Artist,
+                        value: "Radiohead".into(),
+                        exact: false,
+                    },
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Number {
+                        field: NumberField::Year,
+                        comparison: Comparison::Between(Some(1995.0), Some(2000.0)),
+                    },
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Text {
+                        field: TextField::Genre,
+                        value: "rock".into(),
+                        exact: false,
+                    },
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Number {
+                        field: NumberField::Duration,
+                        comparison: Comparison::Greater(300.0),
+                    },
+                },
+                Term {
+                    negated: true,
+                    condition: Condition::Text {
+                        field: TextField::Label,
+                        value: "XL".into(),
+                        exact: false,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_query_has_no_terms() {
+        assert!(terms("").is_empty());
+        assert!(terms("   ").is_empty());
+    }
+
+    #[test]
+    fn parses_free_text() {
+        assert_eq!(
+            terms(r#"blue -"kind of" 'x"#),
+            vec![
+                Term {
+                    negated: false,
+                    condition: Condition::FreeText {
+                        value: "blue".into(),
+                        quoted: false,
+                    },
+                },
+                Term {
+                    negated: true,
+                    condition: Condition::FreeText {
+                        value: "kind of".into(),
+                        quoted: true,
+                    },
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::FreeText {
+                        value: "'x".into(),
+                        quoted: false,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn text_that_is_not_a_field_name_is_free_text() {
+        assert_eq!(
+            condition(":x"),
+            Condition::FreeText {
+                value: ":x".into(),
+                quoted: false,
+            }
+        );
+    }
+
+    #[test]
+    fn field_names_ignore_case_and_have_aliases() {
+        for input in ["albumartist:Low", "Album_Artist:Low", "ALBUMARTIST:Low"] {
+            assert_eq!(
+                condition(input),
+                Condition::Text {
+                    field: TextField::AlbumArtist,
+                    value: "Low".into(),
+                    exact: false,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn leading_equals_matches_whole_values() {
+        assert_eq!(
+            condition("artist:=Low"),
+            Condition::Text {
+                field: TextField::Artist,
+                value: "Low".into(),
+                exact: true,
+            }
+        );
+        assert_eq!(
+            condition(r#"artist:="Low Roar""#),
+            Condition::Text {
+                field: TextField::Artist,
+                value: "Low Roar".into(),
+                exact: true,
+            }
+        );
+    }
+
+    #[test]
+    fn quoted_values_unescape_quotes_and_backslashes() {
+        assert_eq!(
+            condition(r#"title:"say \"hi\" \\ bye""#),
+            Condition::Text {
+                field: TextField::Title,
+                value: r#"say "hi" \ bye"#.into(),
+                exact: false,
+            }
+        );
+    }
+
+    #[test]
+    fn quoted_values_end_at_the_closing_quote() {
+        assert_eq!(terms(r#"title:"a b"c"#).len(), 2);
+    }
+
+    #[test]
+    fn parses_comparison_operators() {
+        assert_eq!(comparison("year:1999"), Comparison::Equal(1999.0));
+        assert_eq!(comparison("year:=1999"), Comparison::Equal(1999.0));
+        assert_eq!(comparison("year:>1999"), Comparison::Greater(1999.0));
+        assert_eq!(
+            comparison("year:>=1999"),
+            Comparison::GreaterOrEqual(1999.0)
+        );
+        assert_eq!(comparison("year:<1999"), Comparison::Less(1999.0));
+        assert_eq!(comparison("year:<=1999"), Comparison::LessOrEqual(1999.0));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(
+            comparison("track:1..3"),
+            Comparison::Between(Some(1.0), Some(3.0))
+        );
+        assert_eq!(
+            comparison("track:3..3"),
+            Comparison::Between(Some(3.0), Some(3.0))
+        );
+        assert_eq!(
+            comparison("year:1995.."),
+            Comparison::Between(Some(1995.0), None)
+        );
+        assert_eq!(
+            comparison("year:..2000"),
+            Comparison::Between(None, Some(2000.0))
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(comparison("duration:>5:00"), Comparison::Greater(300.0));
+        assert_eq!(comparison("duration:3:05.5"), Comparison::Equal(185.5));
+        assert_eq!(comparison("duration:90.5"), Comparison::Equal(90.5));
+        assert_eq!(
+            comparison("duration:2:00..4:30"),
+            Comparison::Between(Some(120.0), Some(270.0))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        assert_eq!(
+            error("year:abc"),
+            r#""year:" expects a whole number, found "abc" (at character 6)"#
+        );
+        assert_eq!(
+            error("track:1.5"),
+            r#""track:" expects a whole number, found "1.5" (at character 7)"#
+        );
+        assert_eq!(
+            error("year:-5"),
+            r#""year:" expects a whole number, found "-5" (at character 6)"#
+        );
+        assert_eq!(
+            error("year:>"),
+            r#""year:" expects a whole number, found "" (at character 6)"#
+        );
+        assert_eq!(
+            error("duration:3:75"),
+            r#""duration:" expects seconds or minutes:seconds, found "3:75" (at character 10)"#
+        );
+        assert_eq!(
+            error("duration:-3"),
+            r#""duration:" expects seconds or minutes:seconds, found "-3" (at character 10)"#
+        );
+        assert_eq!(
+            error("duration:inf"),
+            r#""duration:" expects seconds or minutes:seconds, found "inf" (at character 10)"#
+        );
+    }
+
+    #[test]
+    fn rejects_bad_ranges() {
+        assert_eq!(
+            error("year:.."),
+            r#""year:" needs at least one end of the range (at character 6)"#
+        );
+        assert_eq!(
+            error("year:2000..1990"),
+            r#""year:" has the empty range 2000..1990, whose start is after its end (at character 6)"#
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert_eq!(
+            error("rock Colour:red"),
+            format!(
+                r#"Unknown field "Colour". Known fields are {} (at character 6)"#,
+                FIELDS.map(|(name, _)| name).join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(
+            error("artist:"),
+            r#"Missing a value after "artist:" (at character 8)"#
+        );
+        assert_eq!(
+            error("artist: x"),
+            r#"Missing a value after "artist:" (at character 8)"#
+        );
+        assert_eq!(
+            error(r#"artist:"""#),
+            r#"Missing a value after "artist:" (at character 8)"#
+        );
+        assert_eq!(
+            error("artist:="),
+            r#"Missing a value after "artist:" (at character 8)"#
+        );
+    }
+
+    #[test]
+    fn rejects_a_lone_minus() {
+        assert_eq!(
+            error("rock -"),
+            r#"Nothing to exclude after "-" (at character 6)"#
+        );
+        assert_eq!(
+            error("- rock"),
+            r#"Nothing to exclude after "-" (at character 1)"#
+        );
+    }
+
+    #[test]
+    fn rejects_unclosed_quotes() {
+        assert_eq!(
+            error(r#"title:"blue"#),
+            "Missing the closing quote of this value (at character 7)"
+        );
+        assert_eq!(
+            error(r#""blue \""#),
+            "Missing the closing quote of this value (at character 1)"
+        );
+    }
+
+    #[test]
+    fn rejects_empty_quotes() {
+        assert_eq!(
+            error(r#"rock """#),
+            "Missing the text between these quotes (at character 6)"
+        );
+        assert_eq!(
+            error(r#"-"  ""#),
+            "Missing the text between these quotes (at character 2)"
+        );
+    }
+
+    #[test]
+    fn error_positions_count_characters() {
+        assert_eq!(
+            error("café year:x"),
+            r#""year:" expects a whole number, found "x" (at character 11)"#
+        );
+    }
+
+    #[test]
+    fn conditions_are_anded_and_negated() {
+        let mut query = QueryBuilder::new("SELECT songs.id FROM songs WHERE 1");
+        Query::parse("year:1999 -format:flac")
+            .unwrap()
+            .push_conditions(&mut query);
+        let sql = query.sql();
+        assert!(sql.contains(" AND IFNULL((CAST(substr(songs.year, 1, 4) AS INTEGER) = ?), 0)"));
+        assert!(sql.contains(" AND NOT IFNULL((songs.format LIKE ? ESCAPE '\\'), 0)"));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::AppError;
+use crate::metadata::covers::{self, CoverCache};
+use crate::metadata::{artists, genres};
+use crate::models::LibraryRoot;
+use glob::Pattern;
+use sqlx::SqlitePool;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Glob patterns matched against paths relative to a library root. A pattern
+/// that matches a folder excludes everything inside it.
+pub struct Exclusions {
+    root: String,
+    patterns: Vec<Pattern>,
+}
+
+impl Exclusions {
+    /// Compile a root's exclusion patterns, skipping any that don't parse
+    pub fn new(root: &LibraryRoot) -> Self {
+        Exclusions {
+            root: root.path.clone(),
+            patterns: root
+                .exclusions
+                .iter()
+                .filter_map(|pattern| match Pattern::new(pattern) {
+                    Ok(pattern) => Some(pattern),
+                    Err(e) => {
+                        eprintln!("Ignoring exclusion pattern {:?}: {}", pattern, e);
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        relative
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| {
+                self.patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_path(ancestor))
+            })
+    }
+}
+
+/// Whether a root's folder is there to be scanned. An empty folder counts as
+/// missing when we have songs for it, since that is what an unmounted drive
+/// usually looks like.
+pub fn is_available(root: &LibraryRoot, has_songs: bool) -> bool {
+    match fs::read_dir(&root.path) {
+        Ok(mut entries) => !has_songs || entries.next().is_some(),
+        Err(_) => false,
+    }
+}
+
+pub async fn load_roots(db_pool: &SqlitePool) -> Result<Vec<LibraryRoot>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, path, enabled, watch, exclusions, online FROM library_roots ORDER BY path",
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(rows.iter().map(LibraryRoot::from_row).collect())
+}
+
+pub async fn get_root(
+    db_pool: &SqlitePool,
+    root_id: i64,
+) -> Result<Option<LibraryRoot>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT id, path, enabled, watch, exclusions, online FROM library_roots WHERE id = ?",
+    )
+    .bind(root_id)
+    .fetch_optional(db_pool)
+    .await?;
+
+    Ok(row.as_ref().map(LibraryRoot::from_row))
+}
+
+/// Add the folder at `path` as a library root, or return it if it already is
+/// one. Folders inside or containing another root are refused, since their
+/// songs would belong to both. Songs and albums scanned before roots existed
+/// are adopted by the root containing them.
+pub async fn add_root(db_pool: &SqlitePool, path: &str) -> Result<LibraryRoot, AppError> {
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| AppError::InvalidInput(format!("Can't use {}: {}", path, e)))?;
+
+    for root in load_roots(db_pool).await? {
+        let existing = fs::canonicalize(&root.path).unwrap_or_else(|_| PathBuf::from(&root.path));
+        if existing == canonical {
+            return Ok(root);
+        }
+        if canonical.starts_with(&existing) || existing.starts_with(&canonical) {
+            return Err(AppError::InvalidInput(format!(
+                "{} overlaps the library folder {}",
+                canonical.display(),
+                root.path
+            )));
+        }
+    }
+
+    let path = canonical.display().to_string();
+    sqlx::query("INSERT OR IGNORE INTO library_roots (path) VALUES (?)")
+        .bind(&path)
+        .execute(db_pool)
+        .await?;
+
+    let root = sqlx::query(
+        "SELECT id, path, enabled, watch, exclusions, online FROM library_roots WHERE path = ?",
+    )
+    .bind(&path)
+    .fetch_one(db_pool)
+    .await
+    .map(|row| LibraryRoot::from_row(&row))?;
+
+    let prefix = canonical.join("").display().to_string();
+    sqlx::query(
+        "UPDATE songs SET root_id = ?1 WHERE root_id IS NULL AND substr(path, 1, length(?2)) = ?2",
+    )
+    .bind(root.id)
+    .bind(&prefix)
+    .execute(db_pool)
+    .await?;
+    sqlx::query(
+        r#"
+        UPDATE albums SET root_id = ?1
+        WHERE root_id IS NULL AND (folder_path = ?2 OR substr(folder_path, 1, length(?3)) = ?3)
+        "#,
+    )
+    .bind(root.id)
+    .bind(&path)
+    .bind(&prefix)
+    .execute(db_pool)
+    .await?;
+
+    Ok(root)
+}
+
+/// Remove a root along with all of its songs and albums, and the covers,
+/// artists and genres nothing else uses
+pub async fn remove_root(
+    db_pool: &SqlitePool,
+    covers: &CoverCache,
+    root_id: i64,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("DELETE FROM library_roots WHERE id = ?")
+        .bind(root_id)
+        .execute(&mut *tx)
+        .await?;
+    let unused_covers = covers::prune_covers(&mut tx).await?;
+    artists::prune_artists(&mut tx).await?;
+    genres::prune_genres(&mut tx).await?;
+    tx.commit().await?;
+
+    covers.remove(&unused_covers);
+    Ok(())
+}
+
+pub async fn set_root_options(
+    db_pool: &SqlitePool,
+    root_id: i64,
+    enabled: bool,
+    watch: bool,
+    exclusions: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE library_roots SET enabled = ?, watch = ?, exclusions = ? WHERE id = ?")
+        .bind(enabled)
+        .bind(watch)
+        .bind(serde_json::to_string(exclusions).unwrap_or_else(|_| "[]".into()))
+        .bind(root_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_root_online(
+    db_pool: &SqlitePool,
+    root_id: i64,
+    online: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE library_roots SET online = ? WHERE id = ?")
+        .bind(online)
+        .bind(root_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
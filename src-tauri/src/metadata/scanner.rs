@@ -1,20 +1,46 @@
-use crate::models::{Album, SongInfo};
-use base64::engine::general_purpose::STANDARD;
-use base64::Engine;
+use crate::error::AppError;
+use crate::metadata::artists::{self, ArtistIds};
+use crate::metadata::artwork::{ArtworkFinder, ArtworkKind, SongArtwork};
+use crate::metadata::covers::{self, CoverCache};
+use crate::metadata::genres::{self, GenreIds};
+use crate::metadata::roots::{self, Exclusions};
+use crate::metadata::search;
+use crate::models::{Album, LibraryRoot, ScanPhase, ScanProgress, SongInfo, TagSplitting};
+use lofty::config::ParseOptions;
+use lofty::error::{ErrorKind, LoftyError};
+use lofty::file::FileType;
+use lofty::mp4::{Mp4Codec, Mp4File};
+use lofty::picture::Picture;
 use lofty::prelude::*;
-use lofty::{picture::PictureType, read_from_path};
-use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
-use std::collections::HashMap;
+use lofty::probe::Probe;
+use sqlx::{
+    migrate::{MigrateDatabase, Migrator},
+    QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool,
+};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use symphonia::core::codecs::{
+    CodecType, CODEC_TYPE_AAC, CODEC_TYPE_ALAC, CODEC_TYPE_FLAC, CODEC_TYPE_MP3,
+    CODEC_TYPE_PCM_S16BE, CODEC_TYPE_PCM_S16LE, CODEC_TYPE_VORBIS,
+};
 use tauri::{AppHandle, Emitter};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
+use tokio::task::{self, JoinSet};
+use tokio_util::sync::CancellationToken;
 use walkdir::WalkDir;
 
+/// Schema migrations in `migrations/`, embedded at build time
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Open the library database, creating it if needed, and bring its schema up
+/// to date. The file is backed up before any migration touches it.
 pub async fn initialize_database(
     db_path: &Path,
-) -> Result<SqlitePool, Box<dyn std::error::Error + Send + Sync>> {
+    covers: &CoverCache,
+) -> Result<SqlitePool, AppError> {
     let db_url = format!("sqlite:{}", db_path.display());
 
     println!("Initializing database at: {}", db_url);
@@ -28,123 +54,630 @@ pub async fn initialize_database(
     // Connect to the database
     let pool = SqlitePool::connect(&db_url).await?;
 
-    // Create tables
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS albums (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            title TEXT NOT NULL,
-            artist TEXT NOT NULL,
-            year TEXT,
-            genre TEXT,
-            cover_art_base64 TEXT,
-            song_count INTEGER DEFAULT 0,
-            total_duration REAL DEFAULT 0.0,
-            folder_path TEXT NOT NULL,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(title, artist, folder_path)
-        );
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+    if let Some(backup) = backup_before_migrating(&pool, db_path).await? {
+        println!("Backed up database to {}", backup.display());
+    }
+    upgrade_legacy_schema(&pool).await?;
+    MIGRATOR.run(&pool).await?;
+    covers::move_inline_covers(&pool, covers).await?;
 
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS songs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            album_id INTEGER NOT NULL,
-            title TEXT NOT NULL,
-            artist TEXT NOT NULL,
-            album TEXT NOT NULL,
-            genre TEXT,
-            duration REAL DEFAULT 0.0,
-            path TEXT NOT NULL UNIQUE,
-            lyrics_path TEXT,
-            album_artist TEXT,
-            year TEXT,
-            label TEXT,
-            track_number TEXT,
-            file_modified_time INTEGER,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (album_id) REFERENCES albums (id) ON DELETE CASCADE
-        );
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+    println!("Database initialized successfully");
+    Ok(pool)
+}
 
-    // Create indexes for better performance
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_albums_artist_title ON albums(artist, title);")
-        .execute(&pool)
-        .await?;
+/// Copy the database next to itself when migrations are about to change it,
+/// named after the last migration it had. Returns the copy's path, or `None`
+/// when there was nothing to back up.
+async fn backup_before_migrating(
+    pool: &SqlitePool,
+    db_path: &Path,
+) -> Result<Option<PathBuf>, AppError> {
+    // A brand new database has nothing to lose
+    if !table_exists(pool, "songs").await? {
+        return Ok(None);
+    }
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_songs_album_id ON songs(album_id);")
-        .execute(&pool)
-        .await?;
+    let applied: HashSet<i64> = if table_exists(pool, "_sqlx_migrations").await? {
+        sqlx::query("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| row.get("version"))
+            .collect()
+    } else {
+        HashSet::new()
+    };
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_songs_path ON songs(path);")
-        .execute(&pool)
+    if MIGRATOR
+        .iter()
+        .all(|migration| applied.contains(&migration.version))
+    {
+        return Ok(None);
+    }
+
+    let version = applied.iter().max().copied().unwrap_or(0);
+    let file_name = db_path.file_name().unwrap_or_default().to_string_lossy();
+    let backup = db_path.with_file_name(format!("{}.v{}.bak", file_name, version));
+
+    // VACUUM INTO won't overwrite, and an old backup of the same version is stale
+    if backup.exists() {
+        fs::remove_file(&backup)?;
+    }
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup.display().to_string())
+        .execute(pool)
         .await?;
 
-    println!("Database initialized successfully");
-    Ok(pool)
+    Ok(Some(backup))
 }
 
-pub async fn scan_music_folder(
-    folder_path: String,
-    db_pool: SqlitePool,
-    scan_progress: Arc<RwLock<f32>>,
-    app_handle: AppHandle,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Clear existing data for this folder path
-    sqlx::query("DELETE FROM albums WHERE folder_path = ?")
-        .bind(&folder_path)
-        .execute(&db_pool)
+/// Databases created before migrations existed have whatever columns their
+/// release added. Give them the columns of the first migration, which only
+/// creates what is missing, so it can take over from there.
+async fn upgrade_legacy_schema(pool: &SqlitePool) -> Result<(), AppError> {
+    if table_exists(pool, "_sqlx_migrations").await? {
+        return Ok(());
+    }
+
+    for (table, column, definition) in [
+        ("library_roots", "enabled", "BOOLEAN NOT NULL DEFAULT 1"),
+        ("library_roots", "watch", "BOOLEAN NOT NULL DEFAULT 1"),
+        ("library_roots", "exclusions", "TEXT NOT NULL DEFAULT '[]'"),
+        ("library_roots", "online", "BOOLEAN NOT NULL DEFAULT 1"),
+        (
+            "albums",
+            "root_id",
+            "INTEGER REFERENCES library_roots (id) ON DELETE CASCADE",
+        ),
+        (
+            "songs",
+            "root_id",
+            "INTEGER REFERENCES library_roots (id) ON DELETE CASCADE",
+        ),
+        ("albums", "replaygain_album_gain", "REAL"),
+        ("albums", "replaygain_album_peak", "REAL"),
+        ("songs", "replaygain_track_gain", "REAL"),
+        ("songs", "replaygain_track_peak", "REAL"),
+        ("songs", "replaygain_album_gain", "REAL"),
+        ("songs", "replaygain_album_peak", "REAL"),
+        ("songs", "file_size", "INTEGER"),
+        ("songs", "format", "TEXT"),
+        ("songs", "codec", "TEXT"),
+    ] {
+        add_column_if_missing(pool, table, column, definition).await?;
+    }
+
+    Ok(())
+}
+
+async fn table_exists(pool: &SqlitePool, table: &str) -> Result<bool, sqlx::Error> {
+    Ok(
+        sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(table)
+            .fetch_optional(pool)
+            .await?
+            .is_some(),
+    )
+}
+
+/// Add a column to an existing table unless it is already there. Tables that
+/// don't exist yet are left to the migrations.
+async fn add_column_if_missing(
+    pool: &SqlitePool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), sqlx::Error> {
+    let columns: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info(?)")
+        .bind(table)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| row.get("name"))
+        .collect();
+
+    if !columns.is_empty() && !columns.iter().any(|name| name == column) {
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(pool)
         .await?;
+    }
 
-    // Collect all FLAC files first for progress tracking
-    let flac_files: Vec<_> = WalkDir::new(&folder_path)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| {
-            e.path()
-                .extension()
-                .map(|s| s.to_ascii_lowercase() == "flac")
-                .unwrap_or(false)
-        })
+    Ok(())
+}
+
+/// What the database knows about a file from the last scan
+struct KnownFile {
+    id: i64,
+    album_id: i64,
+    modified_time: Option<i64>,
+    size: Option<i64>,
+}
+
+/// A new or changed file, read and waiting to be written
+struct ScannedSong {
+    info: SongInfo,
+    /// The song's cover and other artwork, already in the cache
+    artwork: SongArtwork,
+    /// Artists and genres named in the tags, split into single names
+    credits: TagValues,
+    /// Plain text of the lyrics, for the search index
+    lyrics: Option<String>,
+    modified_time: Option<i64>,
+    size: Option<i64>,
+}
+
+/// Every value of the multi-value tags of a file
+#[derive(Default)]
+struct TagValues {
+    artists: Vec<String>,
+    album_artists: Vec<String>,
+    genres: Vec<String>,
+    /// Lyrics embedded in the tags
+    lyrics: Option<String>,
+}
+
+/// New and changed songs grouped by album key
+type AlbumGroups = HashMap<String, (Album, Vec<ScannedSong>)>;
+
+/// A file read on a worker, or `None` for one that turned out not to be audio
+type ReadResult = Result<Option<ScannedSong>, FailedFile>;
+
+/// A file a scan couldn't read
+struct FailedFile {
+    path: String,
+    /// "io", "corrupt", "unsupported" or "crash"
+    kind: &'static str,
+    message: String,
+}
+
+impl FailedFile {
+    fn new(path: &Path, error: LoftyError) -> Self {
+        let kind = match error.kind() {
+            ErrorKind::Io(_) => "io",
+            _ => "corrupt",
+        };
+        FailedFile {
+            path: path.display().to_string(),
+            kind,
+            message: error.to_string(),
+        }
+    }
+
+    /// An audio file in a format the player can't decode
+    fn unsupported(path: &Path, file_type: FileType) -> Self {
+        FailedFile {
+            path: path.display().to_string(),
+            kind: "unsupported",
+            message: format!("{:?} files can't be played", file_type),
+        }
+    }
+}
+
+/// Everything a scan has to write, built up as files are read
+#[derive(Default)]
+struct ScanChanges {
+    /// Songs in the scanned folders that haven't been seen yet
+    known_files: HashMap<String, KnownFile>,
+    /// New and changed songs
+    albums: AlbumGroups,
+    /// Songs whose files are gone
+    removed: Vec<KnownFile>,
+    /// Albums whose song list changed and need their totals recomputed
+    touched_albums: HashSet<i64>,
+    /// Files that couldn't be read
+    failures: Vec<FailedFile>,
+    /// Folders whose errors from earlier scans are replaced by `failures`
+    checked_paths: Vec<PathBuf>,
+}
+
+/// Rows written per multi-row statement, well under SQLite's limit on bound parameters
+pub(crate) const WRITE_BATCH: usize = 200;
+
+/// Replace the rows of a link table such as `song_artists` for each owner
+/// with links to the given ids. Repeated ids are linked once.
+pub(crate) async fn replace_links(
+    conn: &mut SqliteConnection,
+    table: &str,
+    owner_column: &str,
+    target_column: &str,
+    links: &[(i64, Vec<i64>)],
+) -> Result<(), sqlx::Error> {
+    for batch in links.chunks(WRITE_BATCH) {
+        let mut query =
+            QueryBuilder::new(format!("DELETE FROM {} WHERE {} IN (", table, owner_column));
+        let mut ids = query.separated(", ");
+        for (owner_id, _) in batch {
+            ids.push_bind(owner_id);
+        }
+        query.push(")").build().execute(&mut *conn).await?;
+    }
+
+    let rows: Vec<(i64, i64)> = links
+        .iter()
+        .flat_map(|(owner_id, targets)| targets.iter().map(move |target| (*owner_id, *target)))
         .collect();
+    for batch in rows.chunks(WRITE_BATCH) {
+        let mut query = QueryBuilder::new(format!(
+            "INSERT OR IGNORE INTO {} ({}, {}) ",
+            table, owner_column, target_column
+        ));
+        query.push_values(batch, |mut row, (owner_id, target)| {
+            row.push_bind(owner_id).push_bind(target);
+        });
+        query.build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
 
-    let total_files = flac_files.len() as f32;
+/// Upper bound on the number of files read at once
+pub const MAX_SCAN_CONCURRENCY: usize = 32;
+
+/// Default number of files read at once: one per core
+pub fn default_scan_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
 
-    // Group songs by album for efficient processing
-    let mut albums_map: HashMap<String, (Album, Vec<SongInfo>)> = HashMap::new();
+/// Reads files on blocking worker threads, at most `concurrency` at a time.
+/// Results come back in the order the reads finish.
+struct FileReader {
+    tasks: JoinSet<ReadResult>,
+    /// Files being read, so a worker that crashes can still be blamed on its file
+    paths: HashMap<task::Id, PathBuf>,
+    concurrency: usize,
+    artwork: ArtworkFinder,
+    splitting: Arc<TagSplitting>,
+}
 
-    for (index, entry) in flac_files.iter().enumerate() {
-        let path = entry.path();
+impl FileReader {
+    fn new(concurrency: usize, covers: &CoverCache, splitting: &TagSplitting) -> Self {
+        FileReader {
+            tasks: JoinSet::new(),
+            paths: HashMap::new(),
+            concurrency: concurrency.clamp(1, MAX_SCAN_CONCURRENCY),
+            artwork: ArtworkFinder::new(covers),
+            splitting: Arc::new(splitting.clone()),
+        }
+    }
 
-        // Update progress
-        let processed_files = index as f32;
-        let progress = if total_files > 0.0 {
-            (processed_files / total_files) * 100.0
+    /// Start reading a file. When every worker is busy, this first waits for
+    /// one of them and returns what it read.
+    async fn push(
+        &mut self,
+        path: PathBuf,
+        modified_time: Option<i64>,
+        size: Option<i64>,
+    ) -> Option<ReadResult> {
+        let finished = if self.tasks.len() >= self.concurrency {
+            self.next().await
         } else {
-            0.0
+            None
         };
 
-        {
-            let mut prog = scan_progress.write().await;
-            *prog = progress;
+        let task_path = path.clone();
+        let finder = self.artwork.clone();
+        let splitting = self.splitting.clone();
+        let task = self.tasks.spawn_blocking(move || {
+            let file_type = match sniff_audio_type(&path) {
+                Ok(Some(file_type)) if is_playable(file_type) => file_type,
+                Ok(Some(file_type)) => return Err(FailedFile::unsupported(&path, file_type)),
+                Ok(None) => return Ok(None),
+                Err(e) => return Err(FailedFile::new(&path, e)),
+            };
+            let (info, values, pictures) =
+                read_audio_file(&path, file_type).map_err(|e| FailedFile::new(&path, e))?;
+            let artwork = finder.find(&path, &info.album, &pictures);
+            let credits = TagValues {
+                artists: splitting.artists(&values.artists),
+                album_artists: splitting.artists(&values.album_artists),
+                genres: splitting.genres(&values.genres),
+                lyrics: None,
+            };
+            let lyrics = search::lyrics_text(info.lyrics_path.as_deref(), values.lyrics.as_deref());
+
+            Ok(Some(ScannedSong {
+                info,
+                artwork,
+                credits,
+                lyrics,
+                modified_time,
+                size,
+            }))
+        });
+        self.paths.insert(task.id(), task_path);
+
+        finished
+    }
+
+    /// Wait for the next read to finish, or `None` once all of them have
+    async fn next(&mut self) -> Option<ReadResult> {
+        match self.tasks.join_next_with_id().await? {
+            Ok((id, read)) => {
+                self.paths.remove(&id);
+                Some(read)
+            }
+            Err(e) => {
+                let path = self.paths.remove(&e.id()).unwrap_or_default();
+                Some(Err(FailedFile {
+                    path: path.display().to_string(),
+                    kind: "crash",
+                    message: e.to_string(),
+                }))
+            }
+        }
+    }
+}
+
+/// Lets commands pause, resume and cancel a running scan. The scanner checks
+/// it between files.
+#[derive(Clone)]
+pub struct ScanControl {
+    cancel: CancellationToken,
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ScanControl {
+    fn default() -> Self {
+        ScanControl {
+            cancel: CancellationToken::new(),
+            paused: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl ScanControl {
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.send_replace(paused);
+    }
+
+    /// Wait while the scan is paused. Returns false once it is cancelled.
+    async fn checkpoint(&self) -> bool {
+        let mut paused = self.paused.subscribe();
+        tokio::select! {
+            _ = paused.wait_for(|paused| !paused) => !self.is_cancelled(),
+            _ = self.cancel.cancelled() => false,
+        }
+    }
+}
+
+/// How often scan progress is sent to the frontend
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Keeps the shared `ScanProgress` up to date and sends it to the frontend as
+/// `scan_progress` events, at most once per `PROGRESS_INTERVAL` except when
+/// the phase changes
+pub struct ProgressReporter {
+    progress: Arc<RwLock<ScanProgress>>,
+    app_handle: AppHandle,
+    last_sent: Option<Instant>,
+    /// When the current root started reading tags, for the ETA
+    reading_since: Instant,
+}
+
+impl ProgressReporter {
+    pub fn new(progress: Arc<RwLock<ScanProgress>>, app_handle: AppHandle) -> Self {
+        ProgressReporter {
+            progress,
+            app_handle,
+            last_sent: None,
+            reading_since: Instant::now(),
+        }
+    }
+
+    /// Apply `change` and send the result, if it is time to or `force` is set
+    async fn update(&mut self, force: bool, change: impl FnOnce(&mut ScanProgress)) {
+        let mut progress = self.progress.write().await;
+        change(&mut progress);
+
+        let due = self
+            .last_sent
+            .is_none_or(|sent| sent.elapsed() >= PROGRESS_INTERVAL);
+        if force || due {
+            self.last_sent = Some(Instant::now());
+            let _ = self.app_handle.emit("scan_progress", progress.clone());
+        }
+    }
+
+    async fn start_root(&mut self, root: &LibraryRoot) {
+        self.update(true, |progress| {
+            progress.phase = ScanPhase::Discovering;
+            progress.current_root = Some(root.path.clone());
+            progress.progress = 0.0;
+            progress.current_file = None;
+            progress.total_files = None;
+            progress.processed_files = None;
+            progress.eta_seconds = None;
+        })
+        .await;
+    }
+
+    async fn start_reading(&mut self, total_files: usize) {
+        self.reading_since = Instant::now();
+        self.update(true, |progress| {
+            progress.phase = ScanPhase::ReadingTags;
+            progress.total_files = Some(total_files as u32);
+            progress.processed_files = Some(0);
+        })
+        .await;
+    }
+
+    async fn file_checked(&mut self, path: &Path) {
+        let elapsed = self.reading_since.elapsed().as_secs_f32();
+        self.update(false, |progress| {
+            let total = progress.total_files.unwrap_or(0);
+            let processed = progress.processed_files.unwrap_or(0) + 1;
+            progress.processed_files = Some(processed);
+            progress.current_file = Some(path.display().to_string());
+            if total > 0 {
+                progress.progress = processed as f32 / total as f32 * 100.0;
+                progress.eta_seconds =
+                    Some(elapsed / processed as f32 * total.saturating_sub(processed) as f32);
+            }
+        })
+        .await;
+    }
+
+    async fn start_writing(&mut self) {
+        self.update(true, |progress| {
+            progress.phase = ScanPhase::WritingDatabase;
+            progress.current_file = None;
+            progress.eta_seconds = None;
+        })
+        .await;
+    }
+
+    async fn root_written(&mut self, (added, updated, removed): (u32, u32, u32)) {
+        self.update(true, |progress| {
+            progress.progress = 100.0;
+            progress.added += added;
+            progress.updated += updated;
+            progress.removed += removed;
+        })
+        .await;
+    }
+
+    /// Mark the scan as over
+    pub async fn finish(&mut self) {
+        self.update(true, |progress| {
+            progress.is_scanning = false;
+            progress.paused = false;
+            progress.phase = ScanPhase::Idle;
+            progress.current_file = None;
+            progress.eta_seconds = None;
+        })
+        .await;
+    }
+}
+
+/// Modification time in seconds and size of a file
+fn file_stamp(path: &Path) -> (Option<i64>, Option<i64>) {
+    let Ok(metadata) = fs::metadata(path) else {
+        return (None, None);
+    };
+    let modified_time = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+    (modified_time, Some(metadata.len() as i64))
+}
+
+/// Songs of a root previously scanned at or below `path`, keyed by file path
+async fn load_known_files(
+    db_pool: &SqlitePool,
+    root_id: i64,
+    path: &Path,
+) -> Result<HashMap<String, KnownFile>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, album_id, path, file_modified_time, file_size
+        FROM songs
+        WHERE root_id = ?1 AND (path = ?2 OR substr(path, 1, length(?3)) = ?3)
+        "#,
+    )
+    .bind(root_id)
+    .bind(path.display().to_string())
+    .bind(path.join("").display().to_string())
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            (
+                row.get("path"),
+                KnownFile {
+                    id: row.get("id"),
+                    album_id: row.get("album_id"),
+                    modified_time: row.get("file_modified_time"),
+                    size: row.get("file_size"),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Queue `path` on `reader` if it is new or changed since the last scan.
+/// Unchanged files are dropped from `known_files` right away; the others are
+/// dropped once read, so what is left at the end of a scan is gone from disk.
+async fn check_file(path: &Path, reader: &mut FileReader, changes: &mut ScanChanges) {
+    let path_string = path.display().to_string();
+    let (modified_time, size) = file_stamp(path);
+
+    // Unchanged since the last scan
+    if let Some(known) = changes.known_files.get(&path_string) {
+        if modified_time.is_some() && known.modified_time == modified_time && known.size == size {
+            changes.known_files.remove(&path_string);
+            return;
         }
+    }
 
-        // Emit progress event to frontend
-        let _ = app_handle.emit("scan_progress", progress);
+    if let Some(read) = reader.push(path.to_path_buf(), modified_time, size).await {
+        changes.add(read);
+    }
+}
 
-        let mut song_info = process_audio_file(path).await?;
+/// Wait for the files still being read and add them to `changes`
+async fn finish_reading(mut reader: FileReader, changes: &mut ScanChanges) {
+    while let Some(read) = reader.next().await {
+        changes.add(read);
+    }
+}
+
+impl ScanChanges {
+    /// Numbers of songs added, updated and removed
+    fn counts(&self) -> (u32, u32, u32) {
+        let (mut added, mut updated) = (0, 0);
+        for (_, songs) in self.albums.values() {
+            for song in songs {
+                match song.info.id {
+                    Some(_) => updated += 1,
+                    None => added += 1,
+                }
+            }
+        }
+        (added, updated, self.removed.len() as u32)
+    }
+
+    /// Add what a worker read: a song goes into its album group, a failure is
+    /// recorded. A known file that can't be read keeps its song.
+    fn add(&mut self, read: ReadResult) {
+        let mut scanned = match read {
+            Ok(Some(scanned)) => scanned,
+            Ok(None) => return,
+            Err(failed) => {
+                eprintln!("Couldn't read {}: {}", failed.path, failed.message);
+                self.known_files.remove(&failed.path);
+                self.failures.push(failed);
+                return;
+            }
+        };
+        let song_info = &mut scanned.info;
+
+        // Keep the id of a changed file, and remember which album it was in
+        if let Some(known) = self.known_files.remove(&song_info.path) {
+            song_info.id = Some(known.id);
+            self.touched_albums.insert(known.album_id);
+        }
 
         // Create album key (artist + album + folder)
-        let album_folder = path.parent().unwrap_or(Path::new("")).display().to_string();
+        let album_folder = Path::new(&song_info.path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .display()
+            .to_string();
         let album_key = format!(
             "{}||{}||{}",
             song_info.album_artist.as_ref().unwrap_or(&song_info.artist),
@@ -153,26 +686,10 @@ pub async fn scan_music_folder(
         );
 
         // Add to albums map or update existing
-        match albums_map.get_mut(&album_key) {
-            Some((album, songs)) => {
-                // Update album duration
-                album.total_duration += song_info.duration;
-                album.song_count += 1;
-
-                // Use cover art from song if album doesn't have one
-                if album.cover_art_base64.is_none() && song_info.cover_art_base64.is_some() {
-                    album.cover_art_base64 = song_info.cover_art_base64.clone();
-                }
-
-                // Remove cover art from song to save space
-                song_info.cover_art_base64 = None;
-                songs.push(song_info);
-            }
+        match self.albums.get_mut(&album_key) {
+            Some((_, songs)) => songs.push(scanned),
             None => {
                 // Create new album
-                let cover_art = song_info.cover_art_base64.clone();
-                song_info.cover_art_base64 = None; // Remove from song to save space
-
                 let album = Album {
                     id: 0, // Will be set when inserted
                     title: song_info.album.clone(),
@@ -182,157 +699,803 @@ pub async fn scan_music_folder(
                         .unwrap_or_else(|| song_info.artist.clone()),
                     year: song_info.year.clone(),
                     genre: song_info.genre.clone(),
-                    cover_art_base64: cover_art,
-                    song_count: 1,
-                    total_duration: song_info.duration,
+                    cover_id: None,
+                    cover_url: None,
+                    song_count: 0,
+                    total_duration: 0.0,
                     folder_path: album_folder,
                 };
 
-                albums_map.insert(album_key, (album, vec![song_info]));
+                self.albums.insert(album_key, (album, vec![scanned]));
+            }
+        }
+    }
+}
+
+/// Write new and changed songs, delete removed ones, fix up album totals and
+/// record the files that couldn't be read, all in one transaction so a
+/// failure leaves the library as it was. Returns the ids of every album that
+/// changed, including ones deleted for being empty.
+async fn write_changes(
+    db_pool: &SqlitePool,
+    covers: &CoverCache,
+    root_id: i64,
+    changes: ScanChanges,
+) -> Result<HashSet<i64>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let mut touched_albums = changes.touched_albums;
+    let mut new_songs = Vec::new();
+    // Artists credited on each written album and song, and genres of each song
+    let mut album_credits = Vec::new();
+    let mut song_credits = Vec::new();
+    let mut song_genres = Vec::new();
+    // Written songs with their lyrics, for the search index
+    let mut song_lyrics = Vec::new();
+
+    for (_, (mut album, songs)) in changes.albums {
+        // Album gain from tags, if any track of the album carries it
+        let album_gain = songs
+            .iter()
+            .map(|song| &song.info)
+            .find(|song| song.replaygain_album_gain.is_some())
+            .map(|song| (song.replaygain_album_gain, song.replaygain_album_peak))
+            .unwrap_or_default();
+
+        // The largest cover of any track
+        let cover = songs
+            .iter()
+            .filter_map(|song| song.artwork.cover.as_ref())
+            .max_by_key(|cover| cover.area());
+        if let Some(cover) = cover {
+            album.cover_id = Some(covers::cover_id(&mut tx, cover).await?);
+        }
+
+        album.id = upsert_album(&mut tx, root_id, &album, album_gain).await?;
+        touched_albums.insert(album.id);
+        add_album_artwork(&mut tx, album.id, &album, &songs).await?;
+        // Album artists come from the first track, like the album's other fields
+        let credits = &songs[0].credits;
+        let album_artists = if credits.album_artists.is_empty() {
+            &credits.artists
+        } else {
+            &credits.album_artists
+        };
+        album_credits.push((album.id, album_artists.clone()));
+
+        for song in songs {
+            match song.info.id {
+                Some(song_id) => {
+                    update_song(&mut tx, root_id, album.id, song_id, &song).await?;
+                    song_credits.push((song_id, song.credits.artists.clone()));
+                    song_genres.push((song_id, song.credits.genres.clone()));
+                    song_lyrics.push((song_id, song.lyrics));
+                }
+                None => new_songs.push((album.id, song)),
             }
         }
     }
 
-    // Insert albums and songs into database
-    for (_, (mut album, songs)) in albums_map {
-        // Insert album
-        let album_id = sqlx::query(
+    for batch in new_songs.chunks(WRITE_BATCH) {
+        let song_ids = insert_songs(&mut tx, root_id, batch).await?;
+        for (_, song) in batch {
+            if let Some(song_id) = song_ids.get(&song.info.path) {
+                song_credits.push((*song_id, song.credits.artists.clone()));
+                song_genres.push((*song_id, song.credits.genres.clone()));
+                song_lyrics.push((*song_id, song.lyrics.clone()));
+            }
+        }
+    }
+
+    let mut artist_ids = ArtistIds::default();
+    artists::link_albums(&mut tx, &mut artist_ids, &album_credits).await?;
+    artists::link_songs(&mut tx, &mut artist_ids, &song_credits).await?;
+    genres::link_songs(&mut tx, &mut GenreIds::default(), &song_genres).await?;
+    search::index_songs(&mut tx, &song_lyrics).await?;
+
+    let removed_ids: Vec<i64> = changes
+        .removed
+        .into_iter()
+        .map(|known| {
+            touched_albums.insert(known.album_id);
+            known.id
+        })
+        .collect();
+    for batch in removed_ids.chunks(WRITE_BATCH) {
+        let mut query = QueryBuilder::new("DELETE FROM songs WHERE id IN (");
+        let mut ids = query.separated(", ");
+        for song_id in batch {
+            ids.push_bind(song_id);
+        }
+        query.push(")").build().execute(&mut *tx).await?;
+    }
+
+    let album_ids: Vec<i64> = touched_albums.iter().copied().collect();
+    for batch in album_ids.chunks(WRITE_BATCH) {
+        refresh_albums(&mut tx, batch).await?;
+    }
+    search::index_albums(&mut tx, &album_ids).await?;
+    let unused_covers = covers::prune_covers(&mut tx).await?;
+    artists::prune_artists(&mut tx).await?;
+    genres::prune_genres(&mut tx).await?;
+
+    for path in &changes.checked_paths {
+        sqlx::query(
             r#"
-            INSERT INTO albums (title, artist, year, genre, cover_art_base64, song_count, total_duration, folder_path)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            DELETE FROM scan_errors
+            WHERE root_id = ?1 AND (path = ?2 OR substr(path, 1, length(?3)) = ?3)
             "#,
         )
-        .bind(&album.title)
-        .bind(&album.artist)
-        .bind(&album.year)
-        .bind(&album.genre)
-        .bind(&album.cover_art_base64)
-        .bind(album.song_count)
-        .bind(album.total_duration)
-        .bind(&album.folder_path)
-        .execute(&db_pool)
-        .await?
-        .last_insert_rowid();
+        .bind(root_id)
+        .bind(path.display().to_string())
+        .bind(path.join("").display().to_string())
+        .execute(&mut *tx)
+        .await?;
+    }
 
-        album.id = album_id;
+    for batch in changes.failures.chunks(WRITE_BATCH) {
+        let mut query =
+            QueryBuilder::new("INSERT OR REPLACE INTO scan_errors (root_id, path, kind, message) ");
+        query.push_values(batch, |mut row, failed| {
+            row.push_bind(root_id)
+                .push_bind(&failed.path)
+                .push_bind(failed.kind)
+                .push_bind(&failed.message);
+        });
+        query.build().execute(&mut *tx).await?;
+    }
 
-        // Insert songs for this album
-        for song in songs {
-            let file_modified_time = fs::metadata(&song.path)
-                .ok()
-                .and_then(|m| m.modified().ok())
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64);
-
-            sqlx::query(
-                r#"
-                INSERT OR REPLACE INTO songs (
-                    album_id, title, artist, album, genre, duration, path, 
-                    lyrics_path, album_artist, year, label, track_number, file_modified_time
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                "#,
-            )
-            .bind(album_id)
-            .bind(&song.title)
-            .bind(&song.artist)
-            .bind(&song.album)
-            .bind(&song.genre)
-            .bind(song.duration)
-            .bind(&song.path)
-            .bind(&song.lyrics_path)
-            .bind(&song.album_artist)
-            .bind(&song.year)
-            .bind(&song.label)
-            .bind(&song.track_number)
-            .bind(file_modified_time)
-            .execute(&db_pool)
-            .await?;
+    tx.commit().await?;
+    covers.remove(&unused_covers);
+    Ok(touched_albums)
+}
+
+/// Files under `path` that aren't excluded from the root
+fn walk_files<'a>(
+    path: &Path,
+    exclusions: &'a Exclusions,
+) -> impl Iterator<Item = walkdir::DirEntry> + 'a {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_entry(|e| !exclusions.is_excluded(e.path()))
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+}
+
+/// Check a root's folder is there before touching its songs, marking the root
+/// offline rather than treating every file as deleted when it isn't
+async fn check_root_online(
+    db_pool: &SqlitePool,
+    root: &LibraryRoot,
+    has_songs: bool,
+) -> Result<bool, sqlx::Error> {
+    let online = roots::is_available(root, has_songs);
+    if online != root.online {
+        roots::set_root_online(db_pool, root.id, online).await?;
+    }
+    if !online {
+        eprintln!("Library root {} is offline, keeping its songs", root.path);
+    }
+    Ok(online)
+}
+
+/// Scan a library root incrementally. Files whose modification time and size
+/// match the last scan are skipped, changed files are re-read in place so their
+/// song ids survive, and songs whose files are gone are removed. A root whose
+/// folder is missing is marked offline and left alone. Files are read on up
+/// to `concurrency` worker threads at once.
+///
+/// The scan waits between files while `control` is paused. If it is cancelled,
+/// the files read so far are still saved but nothing is removed, since the
+/// files not reached yet would look deleted.
+///
+/// Files that can't be read are recorded in `scan_errors` and skipped; an
+/// error is only returned when the scan can't go on at all.
+pub async fn scan_music_folder(
+    root: &LibraryRoot,
+    db_pool: SqlitePool,
+    covers: &CoverCache,
+    splitting: &TagSplitting,
+    concurrency: usize,
+    control: &ScanControl,
+    reporter: &mut ProgressReporter,
+) -> Result<(), AppError> {
+    reporter.start_root(root).await;
+
+    let mut changes = ScanChanges {
+        known_files: load_known_files(&db_pool, root.id, Path::new(&root.path)).await?,
+        ..Default::default()
+    };
+
+    if !check_root_online(&db_pool, root, !changes.known_files.is_empty()).await? {
+        return Ok(());
+    }
+
+    // Collect all files first for progress tracking
+    let exclusions = Exclusions::new(root);
+    let files: Vec<_> = walk_files(Path::new(&root.path), &exclusions).collect();
+
+    reporter.start_reading(files.len()).await;
+    let mut reader = FileReader::new(concurrency, covers, splitting);
+
+    for entry in &files {
+        if !control.checkpoint().await {
+            break;
         }
+
+        check_file(entry.path(), &mut reader, &mut changes).await;
+        reporter.file_checked(entry.path()).await;
     }
 
-    // Final progress update
-    {
-        let mut prog = scan_progress.write().await;
-        *prog = 100.0;
+    finish_reading(reader, &mut changes).await;
+
+    // Files still in known_files disappeared since the last scan
+    if !control.is_cancelled() {
+        let gone = std::mem::take(&mut changes.known_files);
+        changes.removed.extend(gone.into_values());
+        changes.checked_paths.push(PathBuf::from(&root.path));
     }
-    let _ = app_handle.emit("scan_complete", ());
+
+    reporter.start_writing().await;
+    let counts = changes.counts();
+    write_changes(&db_pool, covers, root.id, changes).await?;
+    reporter.root_written(counts).await;
+
+    // Covers of tracks that lost out to another track's were stored but never listed
+    let listed = covers::listed_hashes(&db_pool).await?;
+    let covers = covers.clone();
+    task::spawn_blocking(move || covers.remove_unlisted(&listed)).await?;
 
     Ok(())
 }
 
-async fn process_audio_file(
+/// Bring individual files or folders of a library root up to date, for paths
+/// reported by the watcher. Paths that no longer exist have their songs
+/// removed. Returns the ids of albums that changed.
+pub async fn update_paths(
+    db_pool: &SqlitePool,
+    covers: &CoverCache,
+    splitting: &TagSplitting,
+    root: &LibraryRoot,
+    paths: &[PathBuf],
+    concurrency: usize,
+) -> Result<Vec<i64>, AppError> {
+    // An unmounted drive looks like every file was deleted
+    if !check_root_online(db_pool, root, true).await? {
+        return Ok(Vec::new());
+    }
+
+    let exclusions = Exclusions::new(root);
+    let mut changes = ScanChanges::default();
+
+    // A folder covers everything inside it, so skip paths within other reported ones
+    let outermost = paths.iter().filter(|path| {
+        !paths
+            .iter()
+            .any(|other| other != *path && path.starts_with(other))
+    });
+
+    for path in outermost.filter(|path| !exclusions.is_excluded(path)) {
+        changes.known_files = load_known_files(db_pool, root.id, path).await?;
+        let mut reader = FileReader::new(concurrency, covers, splitting);
+
+        // Walking a file yields just the file; a missing path yields nothing
+        for entry in walk_files(path, &exclusions) {
+            check_file(entry.path(), &mut reader, &mut changes).await;
+        }
+
+        finish_reading(reader, &mut changes).await;
+
+        let gone = std::mem::take(&mut changes.known_files);
+        changes.removed.extend(gone.into_values());
+        changes.checked_paths.push(path.clone());
+    }
+
+    let changed = write_changes(db_pool, covers, root.id, changes).await?;
+    Ok(changed.into_iter().collect())
+}
+
+/// Find the album matching `album`'s title, artist and folder, creating it if
+/// needed, and return its id. New cover art and album gain replace the stored values.
+async fn upsert_album(
+    conn: &mut SqliteConnection,
+    root_id: i64,
+    album: &Album,
+    album_gain: (Option<f32>, Option<f32>),
+) -> Result<i64, sqlx::Error> {
+    let existing: Option<i64> =
+        sqlx::query("SELECT id FROM albums WHERE title = ? AND artist = ? AND folder_path = ?")
+            .bind(&album.title)
+            .bind(&album.artist)
+            .bind(&album.folder_path)
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|row| row.get("id"));
+
+    if let Some(album_id) = existing {
+        sqlx::query(
+            r#"
+            UPDATE albums SET
+                cover_id = COALESCE(?, cover_id),
+                replaygain_album_gain = COALESCE(?, replaygain_album_gain),
+                replaygain_album_peak = COALESCE(?, replaygain_album_peak),
+                root_id = ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(album.cover_id)
+        .bind(album_gain.0)
+        .bind(album_gain.1)
+        .bind(root_id)
+        .bind(album_id)
+        .execute(&mut *conn)
+        .await?;
+        return Ok(album_id);
+    }
+
+    let album_id = sqlx::query(
+        r#"
+        INSERT INTO albums (
+            root_id, title, artist, year, genre, cover_id, song_count, total_duration,
+            folder_path, replaygain_album_gain, replaygain_album_peak
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(root_id)
+    .bind(&album.title)
+    .bind(&album.artist)
+    .bind(&album.year)
+    .bind(&album.genre)
+    .bind(album.cover_id)
+    .bind(album.song_count)
+    .bind(album.total_duration)
+    .bind(&album.folder_path)
+    .bind(album_gain.0)
+    .bind(album_gain.1)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    Ok(album_id)
+}
+
+/// Record the secondary artwork found with an album's songs. Artwork is only
+/// ever added; it goes away with the album.
+async fn add_album_artwork(
+    conn: &mut SqliteConnection,
+    album_id: i64,
+    album: &Album,
+    songs: &[ScannedSong],
+) -> Result<(), sqlx::Error> {
+    let mut artwork: HashMap<i64, ArtworkKind> = HashMap::new();
+    for song in songs {
+        for (kind, image) in &song.artwork.secondary {
+            let cover_id = covers::cover_id(&mut *conn, image).await?;
+            if album.cover_id != Some(cover_id) {
+                artwork.entry(cover_id).or_insert(*kind);
+            }
+        }
+    }
+    if artwork.is_empty() {
+        return Ok(());
+    }
+
+    let mut query =
+        QueryBuilder::new("INSERT OR IGNORE INTO album_artwork (album_id, cover_id, kind) ");
+    query.push_values(&artwork, |mut row, (cover_id, kind)| {
+        row.push_bind(album_id)
+            .push_bind(*cover_id)
+            .push_bind(kind.as_str());
+    });
+    query.build().execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Rewrite the row of a song that was read again. Gains missing from its
+/// tags keep the values computed by loudness analysis while the file's size
+/// is unchanged; a file of another size is analysed again.
+async fn update_song(
+    conn: &mut SqliteConnection,
+    root_id: i64,
+    album_id: i64,
+    song_id: i64,
+    scanned: &ScannedSong,
+) -> Result<(), sqlx::Error> {
+    let song = &scanned.info;
+    sqlx::query(
+        r#"
+        UPDATE songs SET
+            root_id = ?1, album_id = ?2, title = ?3, artist = ?4, album = ?5, genre = ?6,
+            duration = ?7, path = ?8, lyrics_path = ?9, album_artist = ?10, year = ?11,
+            label = ?12, track_number = ?13, file_modified_time = ?14, file_size = ?15,
+            format = ?16, codec = ?17,
+            replaygain_track_gain = COALESCE(
+                ?18, CASE WHEN file_size IS ?15 THEN replaygain_track_gain END
+            ),
+            replaygain_track_peak = COALESCE(
+                ?19, CASE WHEN file_size IS ?15 THEN replaygain_track_peak END
+            ),
+            replaygain_album_gain = COALESCE(
+                ?20, CASE WHEN file_size IS ?15 THEN replaygain_album_gain END
+            ),
+            replaygain_album_peak = COALESCE(
+                ?21, CASE WHEN file_size IS ?15 THEN replaygain_album_peak END
+            ),
+            loudness_analyzed_at = CASE WHEN file_size IS ?15 THEN loudness_analyzed_at END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?22
+        "#,
+    )
+    .bind(root_id)
+    .bind(album_id)
+    .bind(&song.title)
+    .bind(&song.artist)
+    .bind(&song.album)
+    .bind(&song.genre)
+    .bind(song.duration)
+    .bind(&song.path)
+    .bind(&song.lyrics_path)
+    .bind(&song.album_artist)
+    .bind(&song.year)
+    .bind(&song.label)
+    .bind(&song.track_number)
+    .bind(scanned.modified_time)
+    .bind(scanned.size)
+    .bind(&song.format)
+    .bind(&song.codec)
+    .bind(song.replaygain_track_gain)
+    .bind(song.replaygain_track_peak)
+    .bind(song.replaygain_album_gain)
+    .bind(song.replaygain_album_peak)
+    .bind(song_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Insert new songs with one multi-row statement, each with the id of its
+/// album. Returns the new song ids by path.
+async fn insert_songs(
+    conn: &mut SqliteConnection,
+    root_id: i64,
+    songs: &[(i64, ScannedSong)],
+) -> Result<HashMap<String, i64>, sqlx::Error> {
+    let mut query = QueryBuilder::new(
+        r#"
+        INSERT INTO songs (
+            root_id, album_id, title, artist, album, genre, duration, path,
+            lyrics_path, album_artist, year, label, track_number, file_modified_time,
+            file_size, format, codec, replaygain_track_gain, replaygain_track_peak,
+            replaygain_album_gain, replaygain_album_peak
+        )
+        "#,
+    );
+
+    query.push_values(songs, |mut row, (album_id, scanned)| {
+        let song = &scanned.info;
+        row.push_bind(root_id)
+            .push_bind(*album_id)
+            .push_bind(&song.title)
+            .push_bind(&song.artist)
+            .push_bind(&song.album)
+            .push_bind(&song.genre)
+            .push_bind(song.duration)
+            .push_bind(&song.path)
+            .push_bind(&song.lyrics_path)
+            .push_bind(&song.album_artist)
+            .push_bind(&song.year)
+            .push_bind(&song.label)
+            .push_bind(&song.track_number)
+            .push_bind(scanned.modified_time)
+            .push_bind(scanned.size)
+            .push_bind(&song.format)
+            .push_bind(&song.codec)
+            .push_bind(song.replaygain_track_gain)
+            .push_bind(song.replaygain_track_peak)
+            .push_bind(song.replaygain_album_gain)
+            .push_bind(song.replaygain_album_peak);
+    });
+    query.push(" RETURNING id, path");
+
+    let rows = query.build().fetch_all(&mut *conn).await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("path"), row.get("id")))
+        .collect())
+}
+
+/// Recompute the song count and duration of albums, deleting the ones with no songs left
+async fn refresh_albums(conn: &mut SqliteConnection, album_ids: &[i64]) -> Result<(), sqlx::Error> {
+    let mut query = QueryBuilder::new(
+        r#"
+        UPDATE albums SET
+            song_count = (SELECT COUNT(*) FROM songs WHERE album_id = albums.id),
+            total_duration = (SELECT COALESCE(SUM(duration), 0.0) FROM songs WHERE album_id = albums.id),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id IN (
+        "#,
+    );
+    let mut ids = query.separated(", ");
+    for album_id in album_ids {
+        ids.push_bind(album_id);
+    }
+    query.push(")").build().execute(&mut *conn).await?;
+
+    let mut query = QueryBuilder::new("DELETE FROM albums WHERE song_count = 0 AND id IN (");
+    let mut ids = query.separated(", ");
+    for album_id in album_ids {
+        ids.push_bind(album_id);
+    }
+    query.push(")").build().execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Audio types the player can decode, each with the codecs symphonia decodes
+/// it with
+const PLAYABLE_TYPES: [(FileType, &[CodecType]); 7] = [
+    (FileType::Aac, &[CODEC_TYPE_AAC]),
+    (FileType::Aiff, &[CODEC_TYPE_PCM_S16BE]),
+    (FileType::Flac, &[CODEC_TYPE_FLAC]),
+    (FileType::Mpeg, &[CODEC_TYPE_MP3]),
+    (FileType::Mp4, &[CODEC_TYPE_AAC, CODEC_TYPE_ALAC]),
+    (FileType::Vorbis, &[CODEC_TYPE_VORBIS]),
+    (FileType::Wav, &[CODEC_TYPE_PCM_S16LE]),
+];
+
+fn is_playable(file_type: FileType) -> bool {
+    PLAYABLE_TYPES
+        .iter()
+        .any(|(playable, _)| *playable == file_type)
+}
+
+/// Identify a file's audio format from its content, falling back to the
+/// extension. Returns `None` for files that aren't audio.
+fn sniff_audio_type(path: &Path) -> Result<Option<FileType>, LoftyError> {
+    Ok(Probe::open(path)?.guess_file_type()?.file_type())
+}
+
+/// Container and codec names shown in the UI
+fn describe_format(path: &Path, file_type: FileType) -> (&'static str, &'static str) {
+    match file_type {
+        FileType::Aac => ("AAC", "AAC"),
+        FileType::Aiff => ("AIFF", "PCM"),
+        FileType::Flac => ("FLAC", "FLAC"),
+        FileType::Mpeg => ("MP3", "MP3"),
+        FileType::Vorbis => ("Ogg", "Vorbis"),
+        FileType::Wav => ("WAV", "PCM"),
+        FileType::Mp4 => {
+            // MP4 can hold several codecs, so look at the audio track itself
+            let codec = fs::File::open(path)
+                .ok()
+                .and_then(|mut file| {
+                    Mp4File::read_from(&mut file, ParseOptions::new().read_tags(false)).ok()
+                })
+                .map(|mp4| match mp4.properties().codec() {
+                    Mp4Codec::AAC => "AAC",
+                    Mp4Codec::ALAC => "ALAC",
+                    Mp4Codec::MP3 => "MP3",
+                    Mp4Codec::FLAC => "FLAC",
+                    _ => "Unknown",
+                })
+                .unwrap_or("Unknown");
+            ("MP4", codec)
+        }
+        _ => ("Unknown", "Unknown"),
+    }
+}
+
+/// Read a file's tags and lyrics, every value of its multi-value tags, and
+/// the pictures embedded in any of its tags. This blocks, so it runs on a
+/// worker thread.
+fn read_audio_file(
     path: &Path,
-) -> Result<SongInfo, Box<dyn std::error::Error + Send + Sync>> {
+    file_type: FileType,
+) -> Result<(SongInfo, TagValues, Vec<Picture>), LoftyError> {
     let mut info = SongInfo::default();
+    let mut values = TagValues::default();
     info.path = path.display().to_string();
 
+    let (format, codec) = describe_format(path, file_type);
+    info.format = Some(format.to_string());
+    info.codec = Some(codec.to_string());
+
+    // Read with the sniffed type so mislabelled files still parse
+    let file = Probe::open(path)?.set_file_type(file_type).read()?;
+
     // Get file duration
-    if let Ok(file) = read_from_path(path) {
-        info.duration = file.properties().duration().as_secs_f32();
-
-        if let Some(tag) = file.primary_tag() {
-            // Handle Cow<str> types properly
-            info.title = tag
-                .title()
-                .map(|t| t.to_string())
-                .unwrap_or_else(|| "Unknown".to_string());
-            info.artist = tag
-                .artist()
-                .map(|a| a.to_string())
-                .unwrap_or_else(|| "Unknown".to_string());
-            info.album = tag
-                .album()
-                .map(|a| a.to_string())
-                .unwrap_or_else(|| "Unknown".to_string());
-            info.genre = tag.genre().map(|g| g.to_string());
-
-            // Process all tag items
-            for item in tag.items() {
-                if let Some(value_str) = item.value().text() {
-                    match item.key() {
-                        ItemKey::TrackArtist => info.artist = value_str.to_string(),
-                        ItemKey::AlbumArtist => info.album_artist = Some(value_str.to_string()),
-                        ItemKey::AlbumTitle => info.album = value_str.to_string(),
-                        ItemKey::Genre => info.genre = Some(value_str.to_string()),
-                        ItemKey::Year => info.year = Some(value_str.to_string()),
-                        ItemKey::Label => info.label = Some(value_str.to_string()),
-                        ItemKey::TrackNumber => info.track_number = Some(value_str.to_string()),
-                        _ => {}
-                    }
-                }
-            }
+    info.duration = file.properties().duration().as_secs_f32();
 
-            // Extract cover art
-            if let Some(picture) = tag
-                .pictures()
-                .iter()
-                .find(|p| p.pic_type() == PictureType::CoverFront)
-            {
-                info.cover_art_base64 = Some(STANDARD.encode(picture.data()));
-            }
+    if let Some(tag) = file.primary_tag() {
+        // Handle Cow<str> types properly
+        info.title = tag
+            .title()
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| "Unknown".to_string());
+        info.artist = tag
+            .artist()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|| "Unknown".to_string());
+        info.album = tag
+            .album()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|| "Unknown".to_string());
+        info.genre = tag.genre().map(|g| g.to_string());
+
+        // Tags may repeat these fields, one name per value
+        let all_values = |key| {
+            tag.get_strings(&key)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        values = TagValues {
+            artists: all_values(ItemKey::TrackArtist),
+            album_artists: all_values(ItemKey::AlbumArtist),
+            genres: all_values(ItemKey::Genre),
+            lyrics: tag.get_string(&ItemKey::Lyrics).map(str::to_string),
+        };
+        if !values.artists.is_empty() {
+            info.artist = values.artists.join("; ");
+        }
+        if !values.album_artists.is_empty() {
+            info.album_artist = Some(values.album_artists.join("; "));
+        }
+        if !values.genres.is_empty() {
+            info.genre = Some(values.genres.join("; "));
         }
-    }
 
-    // Look for external cover art if not found in tags
-    if info.cover_art_base64.is_none() {
-        if let Some(parent_dir) = path.parent() {
-            for cover_name in &["cover.jpg", "cover.png", "folder.jpg", "album.jpg"] {
-                let cover_path = parent_dir.join(cover_name);
-                if cover_path.exists() {
-                    if let Ok(bytes) = fs::read(&cover_path) {
-                        info.cover_art_base64 = Some(STANDARD.encode(&bytes));
-                        break;
+        // Process all tag items
+        for item in tag.items() {
+            if let Some(value_str) = item.value().text() {
+                match item.key() {
+                    ItemKey::AlbumTitle => info.album = value_str.to_string(),
+                    ItemKey::Year => info.year = Some(value_str.to_string()),
+                    ItemKey::Label => info.label = Some(value_str.to_string()),
+                    ItemKey::TrackNumber => info.track_number = Some(value_str.to_string()),
+                    ItemKey::ReplayGainTrackGain => {
+                        info.replaygain_track_gain = parse_replaygain(value_str)
+                    }
+                    ItemKey::ReplayGainTrackPeak => {
+                        info.replaygain_track_peak = parse_replaygain(value_str)
+                    }
+                    ItemKey::ReplayGainAlbumGain => {
+                        info.replaygain_album_gain = parse_replaygain(value_str)
                     }
+                    ItemKey::ReplayGainAlbumPeak => {
+                        info.replaygain_album_peak = parse_replaygain(value_str)
+                    }
+                    _ => {}
                 }
             }
         }
     }
 
+    let pictures = file
+        .tags()
+        .iter()
+        .flat_map(|tag| tag.pictures())
+        .cloned()
+        .collect();
+
     // Look for lyrics file
     let lrc_path = path.with_extension("lrc");
     if lrc_path.exists() {
         info.lyrics_path = Some(lrc_path.display().to_string());
     }
 
-    Ok(info)
+    Ok((info, values, pictures))
+}
+
+/// Parse a ReplayGain tag value such as "-6.52 dB" or "0.988312"
+fn parse_replaygain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    number.trim().parse().ok().filter(|n: &f32| n.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::decoder::TrackDecoder;
+
+    const FRAMES: usize = 4410;
+
+    /// A tenth of a second of 16-bit mono silence in a WAV file
+    fn wav() -> Vec<u8> {
+        let data_len = FRAMES as u32 * 2;
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((36 + data_len).to_le_bytes());
+        bytes.extend(b"WAVEfmt ");
+        bytes.extend(16u32.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(44_100u32.to_le_bytes());
+        bytes.extend(88_200u32.to_le_bytes());
+        bytes.extend(2u16.to_le_bytes());
+        bytes.extend(16u16.to_le_bytes());
+        bytes.extend(b"data");
+        bytes.extend(data_len.to_le_bytes());
+        bytes.resize(bytes.len() + data_len as usize, 0);
+        bytes
+    }
+
+    /// The same silence in an AIFF file
+    fn aiff() -> Vec<u8> {
+        let data_len = FRAMES as u32 * 2;
+        let mut bytes = b"FORM".to_vec();
+        bytes.extend((4 + 26 + 16 + data_len).to_be_bytes());
+        bytes.extend(b"AIFFCOMM");
+        bytes.extend(18u32.to_be_bytes());
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend((FRAMES as u32).to_be_bytes());
+        bytes.extend(16u16.to_be_bytes());
+        // 44100 as an 80-bit extended float
+        bytes.extend([0x40, 0x0e, 0xac, 0x44, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(b"SSND");
+        bytes.extend((8 + data_len).to_be_bytes());
+        bytes.extend([0; 8]);
+        bytes.resize(bytes.len() + data_len as usize, 0);
+        bytes
+    }
+
+    #[test]
+    fn playable_types_have_decoders() {
+        let codecs = symphonia::default::get_codecs();
+        for (file_type, codec_types) in PLAYABLE_TYPES {
+            for codec_type in codec_types {
+                assert!(
+                    codecs.get_codec(*codec_type).is_some(),
+                    "no decoder for {:?} in {:?} files",
+                    codec_type,
+                    file_type
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reads_and_decodes_pcm_files() {
+        let dir = std::env::temp_dir().join(format!("scanner-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for (name, bytes, file_type) in [
+            ("silence.wav", wav(), FileType::Wav),
+            ("silence.aiff", aiff(), FileType::Aiff),
+        ] {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            assert_eq!(sniff_audio_type(&path).unwrap(), Some(file_type));
+            assert_eq!(TrackDecoder::open(&path).unwrap().count(), FRAMES);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn opus_files_are_reported_as_unsupported() {
+        assert!(symphonia::default::get_codecs()
+            .get_codec(symphonia::core::codecs::CODEC_TYPE_OPUS)
+            .is_none());
+
+        // The start of an Ogg page holding an Opus header
+        let mut bytes = b"OggS".to_vec();
+        bytes.resize(28, 0);
+        bytes.extend(b"OpusHead");
+        bytes.resize(64, 0);
+        let path = std::env::temp_dir().join(format!("scanner-test-{}.opus", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+
+        let file_type = sniff_audio_type(&path).unwrap().unwrap();
+        assert_eq!(file_type, FileType::Opus);
+        assert!(!is_playable(file_type));
+        let failed = FailedFile::unsupported(&path, file_type);
+        assert_eq!(failed.kind, "unsupported");
+        assert_eq!(failed.message, "Opus files can't be played");
+
+        fs::remove_file(&path).unwrap();
+    }
 }
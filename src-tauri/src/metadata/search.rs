@@ -0,0 +1,249 @@
+use crate::metadata::scanner::WRITE_BATCH;
+use crate::models::SnippetPart;
+use sqlx::{QueryBuilder, Row, SqliteConnection, SqlitePool};
+use std::fs;
+use std::path::Path;
+
+/// Marks the start of a match in snippets from the index
+const MATCH_START: char = '\u{2}';
+
+/// Marks the end of a match in snippets from the index
+const MATCH_END: char = '\u{3}';
+
+/// Words of context around the match in snippets
+const SNIPPET_WORDS: u32 = 12;
+
+/// What a search index holds. Each kind has its own table, whose rowids are
+/// the ids of the songs, albums or artists it indexes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchKind {
+    Song,
+    Album,
+    Artist,
+}
+
+impl SearchKind {
+    fn table(&self) -> &'static str {
+        match self {
+            SearchKind::Song => "song_search",
+            SearchKind::Album => "album_search",
+            SearchKind::Artist => "artist_search",
+        }
+    }
+
+    /// Column weights for ranking, in table order: titles count most, lyrics least
+    fn rank(&self) -> &'static str {
+        match self {
+            SearchKind::Song => "bm25(song_search, 10.0, 5.0, 4.0, 3.0, 2.0, 2.0, 1.0)",
+            SearchKind::Album => "bm25(album_search, 10.0, 4.0, 2.0, 2.0)",
+            SearchKind::Artist => "bm25(artist_search)",
+        }
+    }
+}
+
+/// Ids of the songs, albums or artists (`kind`) matching an FTS5
+/// `expression`, best first, each with a snippet of its best matching column
+pub async fn find(
+    db_pool: &SqlitePool,
+    kind: SearchKind,
+    expression: &str,
+    limit: u32,
+) -> Result<Vec<(i64, Vec<SnippetPart>)>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT rowid AS id, snippet({table}, -1, ?, ?, '…', {words}) AS snippet
+        FROM {table}
+        WHERE {table} MATCH ?
+        ORDER BY {rank}
+        LIMIT ?
+        "#,
+        table = kind.table(),
+        words = SNIPPET_WORDS,
+        rank = kind.rank()
+    );
+
+    let rows = sqlx::query(&query)
+        .bind(MATCH_START.to_string())
+        .bind(MATCH_END.to_string())
+        .bind(expression)
+        .bind(limit)
+        .fetch_all(db_pool)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let snippet: String = row.get("snippet");
+            (row.get("id"), parse_snippet(&snippet))
+        })
+        .collect())
+}
+
+/// Rewrite the index rows of songs, each with the text of its lyrics if it
+/// has any. Run after the songs' artist and genre links are written.
+pub async fn index_songs(
+    conn: &mut SqliteConnection,
+    songs: &[(i64, Option<String>)],
+) -> Result<(), sqlx::Error> {
+    for batch in songs.chunks(WRITE_BATCH) {
+        let mut query = QueryBuilder::new("DELETE FROM song_search WHERE rowid IN (");
+        let mut ids = query.separated(", ");
+        for (song_id, _) in batch {
+            ids.push_bind(song_id);
+        }
+        query.push(")").build().execute(&mut *conn).await?;
+
+        let mut query = QueryBuilder::new("WITH lyrics (song_id, text) AS (");
+        query.push_values(batch, |mut row, (song_id, lyrics)| {
+            row.push_bind(song_id).push_bind(lyrics);
+        });
+        query.push(
+            r#"
+            )
+            INSERT INTO song_search (
+                rowid, title, artist, album_artist, album, genre, label, lyrics
+            )
+            SELECT
+                songs.id, songs.title,
+                (
+                    SELECT group_concat(artists.name, ' / ') FROM song_artists
+                    JOIN artists ON artists.id = song_artists.artist_id
+                    WHERE song_artists.song_id = songs.id
+                ),
+                (
+                    SELECT group_concat(artists.name, ' / ') FROM album_artists
+                    JOIN artists ON artists.id = album_artists.artist_id
+                    WHERE album_artists.album_id = songs.album_id
+                ),
+                songs.album,
+                (
+                    SELECT group_concat(genres.name, ' / ') FROM song_genres
+                    JOIN genres ON genres.id = song_genres.genre_id
+                    WHERE song_genres.song_id = songs.id
+                ),
+                songs.label, lyrics.text
+            FROM lyrics
+            JOIN songs ON songs.id = lyrics.song_id
+            "#,
+        );
+        query.build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Rewrite the index rows of albums from their credits and songs. Albums
+/// that no longer exist are skipped.
+pub async fn index_albums(
+    conn: &mut SqliteConnection,
+    album_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    for batch in album_ids.chunks(WRITE_BATCH) {
+        let mut query = QueryBuilder::new("DELETE FROM album_search WHERE rowid IN (");
+        let mut ids = query.separated(", ");
+        for album_id in batch {
+            ids.push_bind(album_id);
+        }
+        query.push(")").build().execute(&mut *conn).await?;
+
+        let mut query = QueryBuilder::new(
+            r#"
+            INSERT INTO album_search (rowid, title, album_artist, genre, label)
+            SELECT
+                albums.id, albums.title,
+                (
+                    SELECT group_concat(artists.name, ' / ') FROM album_artists
+                    JOIN artists ON artists.id = album_artists.artist_id
+                    WHERE album_artists.album_id = albums.id
+                ),
+                (
+                    SELECT group_concat(DISTINCT genres.name) FROM songs
+                    JOIN song_genres ON song_genres.song_id = songs.id
+                    JOIN genres ON genres.id = song_genres.genre_id
+                    WHERE songs.album_id = albums.id
+                ),
+                (
+                    SELECT label FROM songs
+                    WHERE songs.album_id = albums.id AND label IS NOT NULL
+                    LIMIT 1
+                )
+            FROM albums
+            WHERE albums.id IN (
+            "#,
+        );
+        let mut ids = query.separated(", ");
+        for album_id in batch {
+            ids.push_bind(album_id);
+        }
+        query.push(")").build().execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Plain text of a song's lyrics, from its .lrc file or failing that its
+/// tags, with LRC timestamps and header lines removed. This blocks.
+pub fn lyrics_text(lrc_path: Option<&str>, embedded: Option<&str>) -> Option<String> {
+    let from_file = lrc_path.and_then(|path| match fs::read(Path::new(path)) {
+        Ok(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+        Err(e) => {
+            eprintln!("Couldn't read lyrics {}: {}", path, e);
+            None
+        }
+    });
+    let raw = from_file.as_deref().or(embedded)?;
+
+    let lines: Vec<String> = raw
+        .lines()
+        .map(strip_timestamps)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// A lyrics line without its leading "[00:12.34]" timestamps or its word
+/// timestamps like "<00:12.34>". Header lines such as "[ar:Artist]" come out
+/// empty.
+fn strip_timestamps(line: &str) -> String {
+    let mut line = line.trim();
+    while line.starts_with('[') {
+        match line.find(']') {
+            Some(end) => line = line[end + 1..].trim_start(),
+            None => break,
+        }
+    }
+    let words: Vec<&str> = line.split(['<', '>']).step_by(2).collect();
+    words.concat().trim().to_string()
+}
+
+/// FTS5 query for what a user typed: every word must match, the last one as
+/// a prefix so results show up while typing. Words are quoted so characters
+/// FTS5 treats as syntax are just text. `None` when there is nothing to find.
+pub fn match_expression(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", words.join(" ")))
+}
+
+/// Split a snippet marked with `MATCH_START` and `MATCH_END` into parts
+fn parse_snippet(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut matched = false;
+    for text in snippet.split([MATCH_START, MATCH_END]) {
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: text.to_string(),
+                matched,
+            });
+        }
+        matched = !matched;
+    }
+    parts
+}
@@ -0,0 +1,212 @@
+use crate::models::TagSplitting;
+
+impl TagSplitting {
+    /// Individual artists named in the values of an artist tag
+    pub fn artists(&self, values: &[String]) -> Vec<String> {
+        split_names(values, &self.artist_delimiters, &self.exceptions)
+    }
+
+    /// Individual genres named in the values of a genre tag
+    pub fn genres(&self, values: &[String]) -> Vec<String> {
+        split_names(values, &self.genre_delimiters, &self.exceptions)
+    }
+}
+
+/// Split each value at `delimiters`, matched case-insensitively, except
+/// within the names in `exceptions`. Brackets a split leaves behind, as in
+/// "A (feat. B)", are dropped. Names come back trimmed and without repeats.
+fn split_names(values: &[String], delimiters: &[String], exceptions: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for value in values {
+        // The closing bracket still owed by an earlier part of this value
+        let mut pending_close = None;
+
+        for part in split_value(value, delimiters, exceptions) {
+            let mut part = part.trim();
+
+            if let Some((open, close)) = pending_close {
+                if part.ends_with(close) && !part.contains(open) {
+                    part = part[..part.len() - 1].trim_end();
+                    pending_close = None;
+                }
+            }
+            for (open, close) in [('(', ')'), ('[', ']')] {
+                if let Some(stripped) = part.strip_suffix(open) {
+                    part = stripped.trim_end();
+                    pending_close = Some((open, close));
+                }
+            }
+
+            let is_new = !names
+                .iter()
+                .any(|name| name.to_lowercase() == part.to_lowercase());
+            if !part.is_empty() && is_new {
+                names.push(part.to_string());
+            }
+        }
+    }
+
+    names
+}
+
+fn split_value<'a>(value: &'a str, delimiters: &[String], exceptions: &[String]) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < value.len() {
+        if let Some(len) = exceptions.iter().find_map(|name| match_at(value, i, name)) {
+            i += len;
+        } else if let Some(len) = delimiters
+            .iter()
+            .find_map(|delimiter| match_at(value, i, delimiter))
+        {
+            parts.push(&value[start..i]);
+            i += len;
+            start = i;
+        } else {
+            i += value[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Byte length of `pattern` where it matches `value` from byte `i` on. A
+/// pattern that starts or ends with a letter or digit only matches a whole
+/// word there, so "feat." doesn't match inside "Defeat.".
+fn match_at(value: &str, i: usize, pattern: &str) -> Option<usize> {
+    let len = match_len(&value[i..], pattern)?;
+    let before = value[..i].chars().next_back();
+    let after = value[i + len..].chars().next();
+    let joins = |edge: Option<char>, other: Option<char>| {
+        edge.is_some_and(char::is_alphanumeric) && other.is_some_and(char::is_alphanumeric)
+    };
+    if joins(pattern.chars().next(), before) || joins(pattern.chars().next_back(), after) {
+        return None;
+    }
+    Some(len)
+}
+
+/// Byte length of the start of `text` that matches `pattern` ignoring case,
+/// or `None` if it doesn't. An empty pattern never matches.
+fn match_len(text: &str, pattern: &str) -> Option<usize> {
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let mut text_chars = text.char_indices();
+    for expected in pattern.chars() {
+        let (_, actual) = text_chars.next()?;
+        if !actual.to_lowercase().eq(expected.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(text_chars.next().map_or(text.len(), |(i, _)| i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artists(values: &[&str]) -> Vec<String> {
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        TagSplitting::default().artists(&values)
+    }
+
+    fn with_delimiters(delimiters: &[&str], value: &str) -> Vec<String> {
+        let delimiters: Vec<String> = delimiters.iter().map(|d| d.to_string()).collect();
+        split_names(&[value.to_string()], &delimiters, &[])
+    }
+
+    #[test]
+    fn splits_at_each_delimiter() {
+        assert_eq!(
+            artists(&["Alpha; Beta/Gamma feat. Delta & Epsilon"]),
+            ["Alpha", "Beta", "Gamma", "Delta", "Epsilon"]
+        );
+    }
+
+    #[test]
+    fn matches_delimiters_ignoring_case() {
+        assert_eq!(artists(&["Alpha FEAT. Beta"]), ["Alpha", "Beta"]);
+    }
+
+    #[test]
+    fn drops_brackets_left_by_a_split() {
+        assert_eq!(artists(&["Alpha (feat. Beta)"]), ["Alpha", "Beta"]);
+        assert_eq!(artists(&["Alpha [feat. Beta]"]), ["Alpha", "Beta"]);
+        assert_eq!(
+            artists(&["Alpha (feat. Beta & Gamma)"]),
+            ["Alpha", "Beta", "Gamma"]
+        );
+    }
+
+    #[test]
+    fn keeps_brackets_that_are_part_of_a_name() {
+        assert_eq!(artists(&["Alpha (UK); Beta"]), ["Alpha (UK)", "Beta"]);
+    }
+
+    #[test]
+    fn drops_empty_parts_and_repeats() {
+        assert_eq!(
+            artists(&["Alpha;; Beta;", "beta", "ALPHA/Gamma"]),
+            ["Alpha", "Beta", "Gamma"]
+        );
+        assert!(artists(&[" ; "]).is_empty());
+    }
+
+    #[test]
+    fn keeps_exceptions_whole() {
+        assert_eq!(artists(&["AC/DC"]), ["AC/DC"]);
+        assert_eq!(artists(&["ac/dc"]), ["ac/dc"]);
+        assert_eq!(
+            artists(&["Simon & Garfunkel; Hall & Oates"]),
+            ["Simon & Garfunkel", "Hall & Oates"]
+        );
+        assert_eq!(
+            artists(&["Crosby, Stills, Nash & Young feat. AC/DC"]),
+            ["Crosby, Stills, Nash & Young", "AC/DC"]
+        );
+    }
+
+    #[test]
+    fn exceptions_only_match_whole_words() {
+        assert_eq!(artists(&["MAC/DC"]), ["MAC", "DC"]);
+        assert_eq!(artists(&["Marshall & Oates"]), ["Marshall", "Oates"]);
+    }
+
+    #[test]
+    fn does_not_split_inside_words() {
+        assert_eq!(artists(&["Defeat. Now"]), ["Defeat. Now"]);
+        assert_eq!(artists(&["Thefeat. Band"]), ["Thefeat. Band"]);
+        assert_eq!(with_delimiters(&["ft"], "Soft Cell"), ["Soft Cell"]);
+        assert_eq!(with_delimiters(&["ft"], "Loft ft Cell"), ["Loft", "Cell"]);
+        assert_eq!(
+            with_delimiters(&["and"], "Sandy and Band"),
+            ["Sandy", "Band"]
+        );
+    }
+
+    #[test]
+    fn splits_at_punctuation_next_to_words() {
+        assert_eq!(with_delimiters(&[","], "Alpha,Beta"), ["Alpha", "Beta"]);
+        assert_eq!(artists(&["Alpha feat.Beta"]), ["Alpha", "Beta"]);
+    }
+
+    #[test]
+    fn ignores_empty_delimiters() {
+        assert_eq!(with_delimiters(&[""], "Alpha"), ["Alpha"]);
+    }
+
+    #[test]
+    fn genres_use_their_own_delimiters() {
+        let values = vec!["Rock/Pop; Drum & Bass".to_string()];
+        assert_eq!(
+            TagSplitting::default().genres(&values),
+            ["Rock", "Pop", "Drum & Bass"]
+        );
+    }
+}
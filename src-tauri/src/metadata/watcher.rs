@@ -0,0 +1,77 @@
+use crate::error::AppError;
+use crate::metadata::covers::CoverCache;
+use crate::metadata::scanner::{default_scan_concurrency, update_paths};
+use crate::models::{AppState, LibraryRoot, TagSplitting};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{mpsc, Mutex};
+
+/// How long the filesystem has to be quiet before changes are processed
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Watches a library root and feeds changed paths to the scanner. Changes
+/// seen during a scan wait for it to finish. Watching stops when this is
+/// dropped.
+pub struct LibraryWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl LibraryWatcher {
+    pub fn start(
+        root: LibraryRoot,
+        db_pool: SqlitePool,
+        covers: CoverCache,
+        library_writes: Arc<Mutex<()>>,
+        app_handle: AppHandle,
+    ) -> Result<Self, AppError> {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Vec<PathBuf>>();
+
+        let mut debouncer =
+            new_debouncer(DEBOUNCE, move |result: DebounceEventResult| match result {
+                Ok(events) => {
+                    let paths = events.into_iter().map(|event| event.path).collect();
+                    let _ = sender.send(paths);
+                }
+                Err(e) => eprintln!("Library watcher error: {}", e),
+            })
+            .map_err(|e| AppError::Watcher(e.to_string()))?;
+
+        debouncer
+            .watcher()
+            .watch(Path::new(&root.path), RecursiveMode::Recursive)
+            .map_err(|e| AppError::Watcher(e.to_string()))?;
+
+        // Ends once the debouncer, and with it the sender, is dropped
+        tauri::async_runtime::spawn(async move {
+            while let Some(paths) = receiver.recv().await {
+                // Setup starts watchers before the app state is managed
+                let (concurrency, splitting) = match app_handle.try_state::<AppState>() {
+                    Some(app_state) => (
+                        *app_state.scan_concurrency.read().await,
+                        app_state.tag_splitting.read().await.clone(),
+                    ),
+                    None => (default_scan_concurrency(), TagSplitting::default()),
+                };
+
+                let _writing = library_writes.lock().await;
+                match update_paths(&db_pool, &covers, &splitting, &root, &paths, concurrency).await
+                {
+                    Ok(album_ids) if !album_ids.is_empty() => {
+                        let _ = app_handle.emit("library_changed", album_ids);
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Failed to update library from watcher: {}", e),
+                }
+            }
+        });
+
+        Ok(LibraryWatcher {
+            _debouncer: debouncer,
+        })
+    }
+}
@@ -1,12 +1,19 @@
-use serde::Serialize;
-use sqlx::SqlitePool;
+use crate::metadata::artwork::ArtworkKind;
+use crate::metadata::covers::{self, CoverCache};
+use crate::metadata::scanner::ScanControl;
+use crate::metadata::watcher::LibraryWatcher;
+use crate::player::engine::Player;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-
+use tokio::sync::{Mutex, RwLock};
 
 #[derive(Serialize, Clone, Debug)]
 pub struct SongInfo {
     pub id: Option<i64>,
+    pub album_id: Option<i64>,
     pub title: String,
     pub artist: String,
     pub album: String,
@@ -14,17 +21,26 @@ pub struct SongInfo {
     pub duration: f32,
     pub path: String,
     pub lyrics_path: Option<String>,
-    pub cover_art_base64: Option<String>,
     pub album_artist: Option<String>,
     pub year: Option<String>,
     pub label: Option<String>,
     pub track_number: Option<String>,
+    /// Container, e.g. "MP4"
+    pub format: Option<String>,
+    /// Audio codec, e.g. "ALAC"
+    pub codec: Option<String>,
+    /// ReplayGain values in dB, peaks as linear amplitude
+    pub replaygain_track_gain: Option<f32>,
+    pub replaygain_track_peak: Option<f32>,
+    pub replaygain_album_gain: Option<f32>,
+    pub replaygain_album_peak: Option<f32>,
 }
 
 impl Default for SongInfo {
     fn default() -> Self {
         SongInfo {
             id: None,
+            album_id: None,
             title: "Unknown".into(),
             artist: "Unknown".into(),
             album: "Unknown".into(),
@@ -32,11 +48,43 @@ impl Default for SongInfo {
             duration: 0.0,
             path: String::new(),
             lyrics_path: None,
-            cover_art_base64: None,
             album_artist: None,
             year: None,
             label: None,
             track_number: None,
+            format: None,
+            codec: None,
+            replaygain_track_gain: None,
+            replaygain_track_peak: None,
+            replaygain_album_gain: None,
+            replaygain_album_peak: None,
+        }
+    }
+}
+
+impl SongInfo {
+    /// Build a song from a `songs` row. Cover art is never loaded for individual songs.
+    pub fn from_row(row: &SqliteRow) -> Self {
+        SongInfo {
+            id: Some(row.get("id")),
+            album_id: Some(row.get("album_id")),
+            title: row.get("title"),
+            artist: row.get("artist"),
+            album: row.get("album"),
+            genre: row.get("genre"),
+            duration: row.get("duration"),
+            path: row.get("path"),
+            lyrics_path: row.get("lyrics_path"),
+            album_artist: row.get("album_artist"),
+            year: row.get("year"),
+            label: row.get("label"),
+            track_number: row.get("track_number"),
+            format: row.get("format"),
+            codec: row.get("codec"),
+            replaygain_track_gain: row.get("replaygain_track_gain"),
+            replaygain_track_peak: row.get("replaygain_track_peak"),
+            replaygain_album_gain: row.get("replaygain_album_gain"),
+            replaygain_album_peak: row.get("replaygain_album_peak"),
         }
     }
 }
@@ -48,23 +96,361 @@ pub struct Album {
     pub artist: String,
     pub year: Option<String>,
     pub genre: Option<String>,
-    pub cover_art_base64: Option<String>,
+    /// Row in `covers`
+    pub cover_id: Option<i64>,
+    /// Where the webview loads the cover from, at the size the query asked for
+    pub cover_url: Option<String>,
     pub song_count: u32,
     pub total_duration: f32,
     pub folder_path: String,
 }
 
+impl Album {
+    /// Build an album from an `albums` row, with a cover URL for a thumbnail
+    /// at least `cover_size` pixels across, or for the full image
+    pub fn from_row(row: &SqliteRow, cover_size: Option<u32>) -> Self {
+        let cover_id: Option<i64> = row.get("cover_id");
+        Album {
+            id: row.get("id"),
+            title: row.get("title"),
+            artist: row.get("artist"),
+            year: row.get("year"),
+            genre: row.get("genre"),
+            cover_id,
+            cover_url: cover_id.map(|id| covers::cover_url(id, cover_size)),
+            song_count: row.get("song_count"),
+            total_duration: row.get("total_duration"),
+            folder_path: row.get("folder_path"),
+        }
+    }
+}
+
+/// A performer credited on songs or albums
+#[derive(Serialize, Clone, Debug)]
+pub struct Artist {
+    pub id: i64,
+    pub name: String,
+    /// Name to order by, e.g. "Beatles, The"
+    pub sort_name: String,
+    pub album_count: u32,
+    pub track_count: u32,
+}
+
+impl Artist {
+    pub fn from_row(row: &SqliteRow) -> Self {
+        Artist {
+            id: row.get("id"),
+            name: row.get("name"),
+            sort_name: row.get("sort_name"),
+            album_count: row.get("album_count"),
+            track_count: row.get("track_count"),
+        }
+    }
+}
+
+/// A genre, with totals that include its subgenres
+#[derive(Serialize, Clone, Debug)]
+pub struct Genre {
+    pub id: i64,
+    pub name: String,
+    /// The broader genre this one is filed under, if any
+    pub parent_id: Option<i64>,
+    pub album_count: u32,
+    pub track_count: u32,
+    pub total_duration: f32,
+}
+
+impl Genre {
+    pub fn from_row(row: &SqliteRow) -> Self {
+        Genre {
+            id: row.get("id"),
+            name: row.get("name"),
+            parent_id: row.get("parent_id"),
+            album_count: row.get("album_count"),
+            track_count: row.get("track_count"),
+            total_duration: row.get("total_duration"),
+        }
+    }
+}
+
+/// A piece of a search snippet, `matched` when it is text the query found
+#[derive(Serialize, Clone, Debug)]
+pub struct SnippetPart {
+    pub text: String,
+    pub matched: bool,
+}
+
+/// Something a search found, with the text around the match
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchHit<T> {
+    pub item: T,
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// Results of a library search, best matches first in each group
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SearchResults {
+    pub songs: Vec<SearchHit<SongInfo>>,
+    pub albums: Vec<SearchHit<Album>>,
+    pub artists: Vec<SearchHit<Artist>>,
+}
+
+/// A picture of an album besides its cover, such as a back cover or booklet page
+#[derive(Serialize, Clone, Debug)]
+pub struct AlbumArtwork {
+    pub cover_id: i64,
+    pub kind: ArtworkKind,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Where the webview loads the image from, at the size the query asked for
+    pub url: String,
+}
+
+impl AlbumArtwork {
+    /// Build artwork from an `album_artwork` row joined with `covers`
+    pub fn from_row(row: &SqliteRow, size: Option<u32>) -> Self {
+        let cover_id = row.get("cover_id");
+        AlbumArtwork {
+            cover_id,
+            kind: ArtworkKind::parse(row.get("kind")),
+            width: row.get("width"),
+            height: row.get("height"),
+            url: covers::cover_url(cover_id, size),
+        }
+    }
+}
+
+/// A folder the library is scanned from
+#[derive(Serialize, Clone, Debug)]
+pub struct LibraryRoot {
+    pub id: i64,
+    pub path: String,
+    /// Disabled roots are left out of scans and not watched
+    pub enabled: bool,
+    pub watch: bool,
+    /// Glob patterns, relative to the root, for files and folders to skip
+    pub exclusions: Vec<String>,
+    /// False when the folder was missing at the last scan. Its songs are kept.
+    pub online: bool,
+}
+
+impl LibraryRoot {
+    pub fn from_row(row: &SqliteRow) -> Self {
+        let exclusions: String = row.get("exclusions");
+        LibraryRoot {
+            id: row.get("id"),
+            path: row.get("path"),
+            enabled: row.get("enabled"),
+            watch: row.get("watch"),
+            exclusions: serde_json::from_str(&exclusions).unwrap_or_default(),
+            online: row.get("online"),
+        }
+    }
+}
+
 pub struct AppState {
     pub db_pool: SqlitePool,
+    /// Image files behind the `covers` table
+    pub covers: CoverCache,
     pub is_scanning: Arc<RwLock<bool>>,
-    pub scan_progress: Arc<RwLock<f32>>,
+    pub scan_progress: Arc<RwLock<ScanProgress>>,
+    pub is_analyzing: Arc<RwLock<bool>>,
+    /// How many files a scan reads at once
+    pub scan_concurrency: Arc<RwLock<usize>>,
+    /// How scans split artist and genre tags
+    pub tag_splitting: Arc<RwLock<TagSplitting>>,
+    /// Pauses or cancels the current scan
+    pub scan_control: RwLock<ScanControl>,
+    /// Active watchers by library root id
+    pub watchers: Mutex<HashMap<i64, LibraryWatcher>>,
+    /// Held by scans and watcher updates so they never write the same files
+    /// at once
+    pub library_writes: Arc<Mutex<()>>,
+    pub player: Player,
+}
+
+/// A file the last scan of its root couldn't read
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanError {
+    pub id: i64,
+    pub root_id: Option<i64>,
+    pub path: String,
+    /// "io", "corrupt", "unsupported" or "crash"
+    pub kind: String,
+    pub message: String,
+    pub created_at: String,
+}
+
+impl ScanError {
+    pub fn from_row(row: &SqliteRow) -> Self {
+        ScanError {
+            id: row.get("id"),
+            root_id: row.get("root_id"),
+            path: row.get("path"),
+            kind: row.get("kind"),
+            message: row.get("message"),
+            created_at: row.get("created_at"),
+        }
+    }
 }
 
-#[derive(Serialize)]
+/// Payload of the `scan_failed` event, sent when a root's scan had to stop
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanFailure {
+    pub root_id: i64,
+    pub path: String,
+    pub message: String,
+}
+
+/// State of the library scan, sent with `scan_progress` events
+#[derive(Serialize, Clone, Debug, Default)]
 pub struct ScanProgress {
     pub is_scanning: bool,
+    pub paused: bool,
+    pub phase: ScanPhase,
+    /// Path of the library root being scanned
+    pub current_root: Option<String>,
+    /// Percentage of the current root's files checked
     pub progress: f32,
     pub current_file: Option<String>,
     pub total_files: Option<u32>,
     pub processed_files: Option<u32>,
+    /// Estimated seconds left in the current root
+    pub eta_seconds: Option<f32>,
+    /// Songs added, updated and removed so far in this scan
+    pub added: u32,
+    pub updated: u32,
+    pub removed: u32,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanPhase {
+    #[default]
+    Idle,
+    Discovering,
+    ReadingTags,
+    WritingDatabase,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlayerStatus {
+    pub state: PlaybackState,
+    pub song_id: Option<i64>,
+    pub position: f32,
+    pub duration: f32,
+    pub volume: f32,
+}
+
+impl Default for PlayerStatus {
+    fn default() -> Self {
+        PlayerStatus {
+            state: PlaybackState::Stopped,
+            song_id: None,
+            position: 0.0,
+            duration: 0.0,
+            volume: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+impl RepeatMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatMode::Off => "off",
+            RepeatMode::One => "one",
+            RepeatMode::All => "all",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "one" => RepeatMode::One,
+            "all" => RepeatMode::All,
+            _ => RepeatMode::Off,
+        }
+    }
+}
+
+/// Which ReplayGain value the player normalizes loudness with
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl NormalizationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalizationMode::Off => "off",
+            NormalizationMode::Track => "track",
+            NormalizationMode::Album => "album",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "track" => NormalizationMode::Track,
+            "album" => NormalizationMode::Album,
+            _ => NormalizationMode::Off,
+        }
+    }
+}
+
+/// How artist and genre tags are split into individual names
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TagSplitting {
+    /// Text between artist names, matched case-insensitively
+    pub artist_delimiters: Vec<String>,
+    /// Text between genre names
+    pub genre_delimiters: Vec<String>,
+    /// Names that contain a delimiter but are one name, e.g. "AC/DC"
+    pub exceptions: Vec<String>,
+}
+
+impl Default for TagSplitting {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        TagSplitting {
+            artist_delimiters: strings(&[";", "/", "feat.", " & "]),
+            genre_delimiters: strings(&[";", "/"]),
+            exceptions: strings(&[
+                "AC/DC",
+                "Simon & Garfunkel",
+                "Hall & Oates",
+                "Mumford & Sons",
+                "Emerson, Lake & Palmer",
+                "Crosby, Stills, Nash & Young",
+            ]),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QueueSnapshot {
+    pub tracks: Vec<SongInfo>,
+    pub current_index: Option<usize>,
+    pub repeat_mode: RepeatMode,
+    pub shuffled: bool,
 }
@@ -0,0 +1,302 @@
+use crate::player::decoder::TrackDecoder;
+use rodio::source::{SeekError, UniformSourceIterator};
+use rodio::Source;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Track = UniformSourceIterator<TrackDecoder, f32>;
+
+/// The format every track in a chain is converted to before it reaches the sink
+#[derive(Clone, Copy, Debug)]
+pub struct OutputFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+impl OutputFormat {
+    fn samples(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.sample_rate as f64) as u64 * self.channels as u64
+    }
+
+    fn duration(&self, samples: u64) -> Duration {
+        Duration::from_secs_f64(samples as f64 / (self.sample_rate as f64 * self.channels as f64))
+    }
+}
+
+struct Shared {
+    next: Option<Track>,
+    transitions: usize,
+}
+
+/// Values the audio thread tweaks while samples are being pulled. Kept in
+/// atomics so the output callback never has to take a lock per sample.
+struct Controls {
+    /// Samples before the end of the current track at which the next one fades in
+    next_crossfade: AtomicU64,
+    /// Gain the output is ramping towards, as `f32` bits
+    target_gain: AtomicU32,
+    /// Gain change per sample while ramping, as `f32` bits
+    gain_step: AtomicU32,
+}
+
+/// The tail of the previous track while it fades out under the current one
+struct Crossfade {
+    track: Track,
+    channels: u64,
+    length: u64,
+    elapsed: u64,
+}
+
+impl Crossfade {
+    /// Equal-power mix of the outgoing track into `incoming`
+    fn mix(&mut self, incoming: f32) -> f32 {
+        // Step the curve per frame so every channel of a frame gets the same gain
+        let frame_start = self.elapsed - self.elapsed % self.channels;
+        let t = (frame_start as f32 / self.length as f32).min(1.0);
+        self.elapsed += 1;
+        let outgoing = self.track.next().unwrap_or(0.0);
+        outgoing * (t * FRAC_PI_2).cos() + incoming * (t * FRAC_PI_2).sin()
+    }
+}
+
+/// A source that plays tracks back to back with no gap, or overlapping when a
+/// crossfade is set. It is appended to the sink once and keeps pulling the next
+/// track from its shared slot, which the audio thread can fill or replace at any
+/// time until the current track runs out.
+pub struct TrackChain {
+    current: Track,
+    current_len: Option<u64>,
+    outgoing: Option<Crossfade>,
+    format: OutputFormat,
+    shared: Arc<Mutex<Shared>>,
+    controls: Arc<Controls>,
+    gain: f32,
+    played: u64,
+    position: Arc<AtomicU64>,
+}
+
+/// The audio thread's side of a `TrackChain`
+#[derive(Clone)]
+pub struct ChainHandle {
+    format: OutputFormat,
+    shared: Arc<Mutex<Shared>>,
+    controls: Arc<Controls>,
+    position: Arc<AtomicU64>,
+}
+
+impl TrackChain {
+    pub fn new(
+        first: TrackDecoder,
+        start_at: Duration,
+        format: OutputFormat,
+    ) -> (Self, ChainHandle) {
+        let shared = Arc::new(Mutex::new(Shared {
+            next: None,
+            transitions: 0,
+        }));
+        let controls = Arc::new(Controls {
+            next_crossfade: AtomicU64::new(0),
+            target_gain: AtomicU32::new(1.0f32.to_bits()),
+            gain_step: AtomicU32::new(1.0f32.to_bits()),
+        });
+        let played = format.samples(start_at);
+        let position = Arc::new(AtomicU64::new(played));
+        let current = UniformSourceIterator::new(first, format.channels, format.sample_rate);
+
+        let chain = TrackChain {
+            current_len: current.total_duration().map(|d| format.samples(d)),
+            current,
+            outgoing: None,
+            format,
+            shared: shared.clone(),
+            controls: controls.clone(),
+            gain: 1.0,
+            played,
+            position: position.clone(),
+        };
+        let handle = ChainHandle {
+            format,
+            shared,
+            controls,
+            position,
+        };
+
+        (chain, handle)
+    }
+
+    /// Swap in the next track while the current one keeps playing underneath it
+    fn start_crossfade(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        self.controls.next_crossfade.store(0, Ordering::Relaxed);
+
+        let Some(next) = shared.next.take() else {
+            return;
+        };
+        shared.transitions += 1;
+
+        let remaining = self
+            .current_len
+            .map(|len| len.saturating_sub(self.played))
+            .unwrap_or(0);
+        self.current_len = next.total_duration().map(|d| self.format.samples(d));
+        let previous = std::mem::replace(&mut self.current, next);
+        self.outgoing = Some(Crossfade {
+            track: previous,
+            channels: self.format.channels as u64,
+            length: remaining.max(1),
+            elapsed: 0,
+        });
+        self.played = 0;
+    }
+
+    #[inline]
+    fn crossfade_due(&self) -> bool {
+        let crossfade = self.controls.next_crossfade.load(Ordering::Relaxed);
+        crossfade > 0
+            && self.outgoing.is_none()
+            // Only start on a frame boundary so channels stay lined up
+            && self.played.is_multiple_of(self.format.channels as u64)
+            && self
+                .current_len
+                .is_some_and(|len| len.saturating_sub(self.played) <= crossfade)
+    }
+
+    #[inline]
+    fn apply_gain(&mut self, sample: f32) -> f32 {
+        let target = f32::from_bits(self.controls.target_gain.load(Ordering::Relaxed));
+        if self.gain != target {
+            let step = f32::from_bits(self.controls.gain_step.load(Ordering::Relaxed));
+            self.gain = if self.gain < target {
+                (self.gain + step).min(target)
+            } else {
+                (self.gain - step).max(target)
+            };
+        }
+        sample * self.gain
+    }
+}
+
+impl ChainHandle {
+    /// Set the track that follows the current one, replacing any earlier choice.
+    /// With a non-zero `crossfade` the next track starts that long before the
+    /// current one ends. Does nothing and returns false if the chain has already
+    /// moved past the track `seen_transitions` refers to, so a stale pick can
+    /// never be played.
+    pub fn set_next(
+        &self,
+        seen_transitions: usize,
+        next: Option<TrackDecoder>,
+        crossfade: Duration,
+    ) -> bool {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.transitions != seen_transitions {
+            return false;
+        }
+
+        let crossfade = match next {
+            Some(_) => self.format.samples(crossfade),
+            None => 0,
+        };
+        shared.next = next.map(|decoder| {
+            UniformSourceIterator::new(decoder, self.format.channels, self.format.sample_rate)
+        });
+        self.controls
+            .next_crossfade
+            .store(crossfade, Ordering::Relaxed);
+        true
+    }
+
+    /// How many times the chain has moved on to a following track
+    pub fn transitions(&self) -> usize {
+        self.shared.lock().unwrap().transitions
+    }
+
+    /// Playback position within the current track
+    pub fn position(&self) -> Duration {
+        self.format.duration(self.position.load(Ordering::Relaxed))
+    }
+
+    /// Ramp the output gain to `target` over `duration`
+    pub fn fade_to(&self, target: f32, duration: Duration) {
+        let samples = self.format.samples(duration).max(1);
+        self.controls
+            .gain_step
+            .store((1.0 / samples as f32).to_bits(), Ordering::Relaxed);
+        self.controls
+            .target_gain
+            .store(target.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Iterator for TrackChain {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if self.crossfade_due() {
+                self.start_crossfade();
+            }
+
+            if let Some(sample) = self.current.next() {
+                self.played += 1;
+                self.position.store(self.played, Ordering::Relaxed);
+
+                let sample = match &mut self.outgoing {
+                    Some(crossfade) if crossfade.elapsed < crossfade.length => {
+                        crossfade.mix(sample)
+                    }
+                    Some(_) => {
+                        self.outgoing = None;
+                        sample
+                    }
+                    None => sample,
+                };
+                return Some(self.apply_gain(sample));
+            }
+
+            self.outgoing = None;
+            let mut shared = self.shared.lock().unwrap();
+            self.current = shared.next.take()?;
+            shared.transitions += 1;
+            self.controls.next_crossfade.store(0, Ordering::Relaxed);
+            self.current_len = self
+                .current
+                .total_duration()
+                .map(|d| self.format.samples(d));
+            self.played = 0;
+        }
+    }
+}
+
+impl Source for TrackChain {
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.format.channels
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.current.try_seek(pos)?;
+        self.outgoing = None;
+        self.played = self.format.samples(pos);
+        self.position.store(self.played, Ordering::Relaxed);
+        Ok(())
+    }
+}
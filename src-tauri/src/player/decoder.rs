@@ -0,0 +1,220 @@
+use rodio::source::SeekError;
+use rodio::Source;
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+use symphonia::core::audio::{AudioBufferRef, Channels, SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Consecutive undecodable packets tolerated before a track is given up on
+const MAX_DECODE_ERRORS: usize = 3;
+
+/// A single audio file decoded with symphonia into interleaved `f32` samples.
+///
+/// Gapless mode is enabled on the format reader, so encoder delay and padding
+/// are trimmed and consecutive tracks join up sample-accurately.
+pub struct TrackDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    spec: SignalSpec,
+    buffer: SampleBuffer<f32>,
+    offset: usize,
+    total_duration: Option<Duration>,
+    gain: f32,
+}
+
+impl TrackDecoder {
+    /// Open `path` and decode its first packet right away, so the track can start
+    /// the moment it is needed.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let format_options = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            mss,
+            &format_options,
+            &MetadataOptions::default(),
+        )?;
+        let format = probed.format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(Error::Unsupported("no decodable audio track"))?;
+
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+        let track_id = track.id;
+        let total_duration = track
+            .codec_params
+            .time_base
+            .zip(track.codec_params.n_frames)
+            .map(|(base, frames)| {
+                let time = base.calc_time(frames);
+                Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+            });
+        let spec = SignalSpec::new(
+            track.codec_params.sample_rate.unwrap_or(44_100),
+            track
+                .codec_params
+                .channels
+                .unwrap_or(Channels::FRONT_LEFT | Channels::FRONT_RIGHT),
+        );
+
+        let mut decoder = TrackDecoder {
+            format,
+            decoder,
+            track_id,
+            spec,
+            buffer: SampleBuffer::new(0, spec),
+            offset: 0,
+            total_duration,
+            gain: 1.0,
+        };
+
+        if !decoder.decode_next_packet() {
+            return Err(Error::DecodeError("no audio data"));
+        }
+
+        Ok(decoder)
+    }
+
+    /// Scale every sample by `gain`, used for loudness normalization
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    /// Decode the next non-empty packet of our track into `buffer`. Returns false at
+    /// the end of the stream or after too many consecutive decode errors.
+    fn decode_next_packet(&mut self) -> bool {
+        let mut errors = 0;
+
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => return false,
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                // Packets trimmed away entirely by gapless processing decode to nothing
+                Ok(decoded) if decoded.frames() == 0 => continue,
+                Ok(decoded) => {
+                    self.spec = fill_buffer(&mut self.buffer, decoded);
+                    self.offset = 0;
+                    return true;
+                }
+                Err(Error::DecodeError(_)) if errors < MAX_DECODE_ERRORS => errors += 1,
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+/// Copy a decoded packet into `buffer`, growing it if needed, and return its format
+fn fill_buffer(buffer: &mut SampleBuffer<f32>, decoded: AudioBufferRef) -> SignalSpec {
+    let spec = *decoded.spec();
+    if buffer.capacity() < decoded.capacity() * spec.channels.count() {
+        *buffer = SampleBuffer::new(decoded.capacity() as u64, spec);
+    }
+    buffer.copy_interleaved_ref(decoded);
+    spec
+}
+
+impl Iterator for TrackDecoder {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<f32> {
+        let sample = *self.buffer.samples().get(self.offset)?;
+        self.offset += 1;
+
+        // Refill straight away: rodio reads a frame length of zero as the end of the source
+        if self.offset >= self.buffer.len() && !self.decode_next_packet() {
+            self.buffer.clear();
+            self.offset = 0;
+        }
+
+        Some(sample * self.gain)
+    }
+}
+
+impl Source for TrackDecoder {
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.buffer.len() - self.offset)
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.spec.channels.count() as u16
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.spec.rate
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let seeked = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: pos.as_secs_f64().into(),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(|e| SeekError::Other(Box::new(e)))?;
+        self.decoder.reset();
+
+        // The reader lands on a packet boundary at or before the requested
+        // timestamp; decode forward and drop the frames in between.
+        loop {
+            let packet = self
+                .format
+                .next_packet()
+                .map_err(|e| SeekError::Other(Box::new(e)))?;
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            if packet.ts() + packet.dur() <= seeked.required_ts {
+                continue;
+            }
+
+            let skip_frames = seeked.required_ts.saturating_sub(packet.ts()) as usize;
+            let decoded = self
+                .decoder
+                .decode(&packet)
+                .map_err(|e| SeekError::Other(Box::new(e)))?;
+            self.spec = fill_buffer(&mut self.buffer, decoded);
+            self.offset = (skip_frames * self.channels() as usize).min(self.buffer.len());
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,631 @@
+use crate::error::AppError;
+use crate::models::{NormalizationMode, PlaybackState, PlayerStatus, SongInfo};
+use crate::player::chain::{ChainHandle, OutputFormat, TrackChain};
+use crate::player::decoder::TrackDecoder;
+use crate::player::queue::{save_position, save_queue, PlayQueue};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{OutputStream, Sink, Source};
+use sqlx::SqlitePool;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex, RwLock};
+
+/// How often the audio thread wakes up to report the playback position
+const POSITION_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the playback position is written to the database while playing
+const POSITION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Length of the fades applied around pause, stop and seek so they don't click
+const CLICK_FADE: Duration = Duration::from_millis(40);
+
+/// Longest crossfade that can be configured
+pub const MAX_CROSSFADE_SECONDS: f32 = 12.0;
+
+pub enum PlayerCommand {
+    /// Load the current queue entry and start it from `start_at` seconds,
+    /// or leave it paused there
+    PlayCurrent {
+        start_at: f32,
+        paused: bool,
+    },
+    Pause,
+    Resume,
+    Stop,
+    Seek(f32),
+    SetVolume(f32),
+    /// Crossfade length in seconds between tracks, 0 for none
+    SetCrossfade(f32),
+    /// Which ReplayGain value to normalize with. Applies from the next track loaded.
+    SetNormalization(NormalizationMode),
+    /// The queue was edited, so the preloaded next track may be stale
+    QueueChanged,
+}
+
+/// Handle to the audio thread. Cloning it is cheap and every clone talks to the same sink.
+#[derive(Clone)]
+pub struct Player {
+    commands: Sender<PlayerCommand>,
+    status: Arc<RwLock<PlayerStatus>>,
+    queue: Arc<Mutex<PlayQueue>>,
+    db_pool: SqlitePool,
+    app_handle: AppHandle,
+}
+
+impl Player {
+    /// Spawn the dedicated audio thread. The rodio output stream is not `Send`,
+    /// so it has to be created and kept alive on the thread that owns the sink.
+    /// A restored queue is cued up paused at `position`.
+    pub fn spawn(
+        app_handle: AppHandle,
+        db_pool: SqlitePool,
+        queue: PlayQueue,
+        position: f32,
+    ) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let status = Arc::new(RwLock::new(PlayerStatus::default()));
+        let has_current = queue.current().is_some();
+        let queue = Arc::new(Mutex::new(queue));
+
+        let audio_thread = AudioThread {
+            status: status.clone(),
+            queue: queue.clone(),
+            db_pool: db_pool.clone(),
+            app_handle: app_handle.clone(),
+            format: output_format(),
+            crossfade: Duration::ZERO,
+            normalization: NormalizationMode::Off,
+            chain: None,
+            seen_transitions: 0,
+            preloaded: None,
+        };
+        thread::Builder::new()
+            .name("audio".into())
+            .spawn(move || audio_thread.run(receiver))
+            .expect("failed to spawn audio thread");
+
+        let player = Player {
+            commands,
+            status,
+            queue,
+            db_pool,
+            app_handle,
+        };
+
+        if has_current {
+            let _ = player.send(PlayerCommand::PlayCurrent {
+                start_at: position,
+                paused: true,
+            });
+        }
+
+        player
+    }
+
+    pub fn send(&self, command: PlayerCommand) -> Result<(), AppError> {
+        self.commands
+            .send(command)
+            .map_err(|_| AppError::Player("Audio thread is not running".to_string()))
+    }
+
+    pub async fn status(&self) -> PlayerStatus {
+        self.status.read().await.clone()
+    }
+
+    pub fn queue(&self) -> &Arc<Mutex<PlayQueue>> {
+        &self.queue
+    }
+
+    /// Persist the queue and notify the frontend. Call this with the queue lock
+    /// still held so saves can't interleave.
+    pub async fn commit_queue(&self, queue: &PlayQueue) -> Result<(), AppError> {
+        let status = self.status().await;
+        let position = if queue.current().and_then(|song| song.id) == status.song_id {
+            status.position
+        } else {
+            0.0
+        };
+
+        save_queue(&self.db_pool, queue, position).await?;
+
+        let _ = self.app_handle.emit("queue_changed", queue.snapshot());
+        self.send(PlayerCommand::QueueChanged)
+    }
+}
+
+struct AudioThread {
+    status: Arc<RwLock<PlayerStatus>>,
+    queue: Arc<Mutex<PlayQueue>>,
+    db_pool: SqlitePool,
+    app_handle: AppHandle,
+    format: OutputFormat,
+    crossfade: Duration,
+    normalization: NormalizationMode,
+    /// The chain currently in the sink, if anything is loaded
+    chain: Option<ChainHandle>,
+    /// Chain transitions already reflected in the queue
+    seen_transitions: usize,
+    /// Song id sitting in the chain's next slot
+    preloaded: Option<i64>,
+}
+
+/// The default output device's format, so tracks are converted once and then
+/// passed through the mixer untouched
+fn output_format() -> OutputFormat {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.default_output_config().ok())
+        .map(|config| OutputFormat {
+            channels: config.channels(),
+            sample_rate: config.sample_rate().0,
+        })
+        .unwrap_or(OutputFormat {
+            channels: 2,
+            sample_rate: 44_100,
+        })
+}
+
+impl AudioThread {
+    fn run(mut self, receiver: Receiver<PlayerCommand>) {
+        let (_stream, stream_handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("Failed to open audio output: {}", e);
+                return;
+            }
+        };
+
+        let sink = match Sink::try_new(&stream_handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                eprintln!("Failed to create audio sink: {}", e);
+                return;
+            }
+        };
+
+        let mut last_saved = Instant::now();
+
+        loop {
+            match receiver.recv_timeout(POSITION_INTERVAL) {
+                Ok(command) => {
+                    if let Err(e) = self.handle_command(command, &sink) {
+                        eprintln!("Playback error: {}", e);
+                        let _ = self.app_handle.emit("player_error", e);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            self.follow_chain();
+
+            if self.status.blocking_read().state != PlaybackState::Playing {
+                continue;
+            }
+
+            if sink.empty() {
+                // The chain ran out without a following track
+                if let Err(e) = self.advance(&sink) {
+                    eprintln!("Playback error: {}", e);
+                    let _ = self.app_handle.emit("player_error", e);
+                }
+                continue;
+            }
+
+            let Some(chain) = &self.chain else {
+                continue;
+            };
+            let position = chain.position().as_secs_f32();
+            self.status.blocking_write().position = position;
+            let _ = self.app_handle.emit("playback_position", position);
+
+            if last_saved.elapsed() >= POSITION_SAVE_INTERVAL {
+                self.save_position(position);
+                last_saved = Instant::now();
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: PlayerCommand, sink: &Sink) -> Result<(), String> {
+        match command {
+            PlayerCommand::PlayCurrent { start_at, paused } => {
+                self.play_current(sink, start_at, paused)?;
+            }
+            PlayerCommand::Pause => {
+                self.fade_out();
+                sink.pause();
+                let position = self.position();
+                self.update_status(|status| {
+                    if status.state == PlaybackState::Playing {
+                        status.state = PlaybackState::Paused;
+                        status.position = position;
+                    }
+                });
+                self.save_position(position);
+            }
+            PlayerCommand::Resume => {
+                let state = self.status.blocking_read().state;
+                match state {
+                    PlaybackState::Paused => {
+                        sink.play();
+                        if let Some(chain) = &self.chain {
+                            chain.fade_to(1.0, CLICK_FADE);
+                        }
+                        self.update_status(|status| status.state = PlaybackState::Playing);
+                    }
+                    PlaybackState::Stopped => self.play_current(sink, 0.0, false)?,
+                    PlaybackState::Playing => {}
+                }
+            }
+            PlayerCommand::Stop => {
+                self.fade_out();
+                sink.stop();
+                self.chain = None;
+                self.preloaded = None;
+                self.update_status(|status| {
+                    status.state = PlaybackState::Stopped;
+                    status.song_id = None;
+                    status.position = 0.0;
+                    status.duration = 0.0;
+                });
+                self.save_position(0.0);
+            }
+            PlayerCommand::Seek(seconds) => {
+                // A seek order on an empty sink would linger and apply to the next track
+                if sink.empty() {
+                    return Ok(());
+                }
+                let Ok(target) = Duration::try_from_secs_f32(seconds.max(0.0)) else {
+                    return Ok(());
+                };
+                let faded = self.fade_out();
+                let result = sink.try_seek(target);
+                if let (true, Some(chain)) = (faded, &self.chain) {
+                    chain.fade_to(1.0, CLICK_FADE);
+                }
+                result.map_err(|e| e.to_string())?;
+                self.update_status(|status| status.position = target.as_secs_f32());
+            }
+            PlayerCommand::SetVolume(volume) => {
+                let volume = volume.clamp(0.0, 1.0);
+                sink.set_volume(volume);
+                self.update_status(|status| status.volume = volume);
+            }
+            PlayerCommand::SetCrossfade(seconds) => {
+                // A corrupt setting turns crossfading off rather than failing
+                self.crossfade =
+                    Duration::try_from_secs_f32(seconds.clamp(0.0, MAX_CROSSFADE_SECONDS))
+                        .unwrap_or_default();
+                // The preloaded track was queued with the old crossfade length
+                self.preloaded = None;
+                self.preload_next();
+            }
+            PlayerCommand::SetNormalization(mode) => {
+                self.normalization = mode;
+                // The preloaded track was opened with the old gain
+                self.preloaded = None;
+                self.preload_next();
+            }
+            PlayerCommand::QueueChanged => {
+                self.follow_chain();
+                self.preload_next();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace whatever the sink is playing with the queue's current track
+    fn play_current(&mut self, sink: &Sink, start_at: f32, paused: bool) -> Result<(), String> {
+        let song = self.queue.blocking_lock().current().cloned();
+
+        let Some(song) = song else {
+            sink.stop();
+            self.chain = None;
+            self.preloaded = None;
+            self.update_status(|status| {
+                *status = PlayerStatus {
+                    volume: status.volume,
+                    ..PlayerStatus::default()
+                }
+            });
+            return Ok(());
+        };
+
+        let mut decoder = match TrackDecoder::open(Path::new(&song.path)) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                sink.stop();
+                self.chain = None;
+                self.preloaded = None;
+                self.update_status(|status| {
+                    status.state = PlaybackState::Stopped;
+                    status.song_id = song.id;
+                    status.position = 0.0;
+                    status.duration = song.duration;
+                });
+                return Err(format!("{}: {}", song.path, e));
+            }
+        };
+
+        decoder.set_gain(normalization_gain(&song, self.normalization));
+
+        let mut start_at = Duration::try_from_secs_f32(start_at.max(0.0)).unwrap_or_default();
+        if !start_at.is_zero() {
+            if let Err(e) = decoder.try_seek(start_at) {
+                eprintln!("Failed to restore position in {}: {}", song.path, e);
+                start_at = Duration::ZERO;
+            }
+        }
+
+        let (chain, handle) = TrackChain::new(decoder, start_at, self.format);
+        self.fade_out();
+        sink.stop();
+        sink.append(chain);
+        if paused {
+            // Start silent so resuming fades in
+            handle.fade_to(0.0, Duration::ZERO);
+            sink.pause();
+        } else {
+            sink.play();
+        }
+
+        self.chain = Some(handle);
+        self.seen_transitions = 0;
+        self.preloaded = None;
+
+        self.update_status(|status| {
+            status.state = if paused {
+                PlaybackState::Paused
+            } else {
+                PlaybackState::Playing
+            };
+            status.song_id = song.id;
+            status.position = start_at.as_secs_f32();
+            status.duration = song.duration;
+        });
+
+        self.preload_next();
+        Ok(())
+    }
+
+    /// Pre-decode the track that comes after the current one and hand it to the
+    /// chain, so it starts on the very next sample once the current track drains
+    fn preload_next(&mut self) {
+        let Some(chain) = &self.chain else {
+            return;
+        };
+
+        let (current, next) = {
+            let queue = self.queue.blocking_lock();
+            let next = queue
+                .peek_advance()
+                .and_then(|index| queue.get(index))
+                .cloned();
+            (queue.current().cloned(), next)
+        };
+
+        let next_id = next.as_ref().and_then(|song| song.id);
+        if next_id.is_some() && next_id == self.preloaded {
+            return;
+        }
+
+        let decoder =
+            next.as_ref()
+                .and_then(|song| match TrackDecoder::open(Path::new(&song.path)) {
+                    Ok(mut decoder) => {
+                        decoder.set_gain(normalization_gain(song, self.normalization));
+                        Some(decoder)
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to preload {}: {}", song.path, e);
+                        None
+                    }
+                });
+
+        let crossfade = match (&current, &next) {
+            (Some(current), Some(next)) if !is_continuation(current, next) => self.crossfade,
+            _ => Duration::ZERO,
+        };
+
+        if chain.set_next(self.seen_transitions, decoder, crossfade) {
+            self.preloaded = next_id;
+        }
+    }
+
+    /// Catch the queue up after the chain moved on to the preloaded track
+    fn follow_chain(&mut self) {
+        let Some(chain) = &self.chain else {
+            return;
+        };
+
+        let transitions = chain.transitions();
+        if transitions == self.seen_transitions {
+            return;
+        }
+
+        let song = {
+            let mut queue = self.queue.blocking_lock();
+            for _ in self.seen_transitions..transitions {
+                queue.advance();
+            }
+            if let Err(e) = tauri::async_runtime::block_on(save_queue(&self.db_pool, &queue, 0.0)) {
+                eprintln!("Failed to save queue: {}", e);
+            }
+            let _ = self.app_handle.emit("queue_changed", queue.snapshot());
+            queue.current().cloned()
+        };
+
+        self.seen_transitions = transitions;
+        self.preloaded = None;
+
+        self.update_status(|status| {
+            status.song_id = song.as_ref().and_then(|song| song.id);
+            status.duration = song.as_ref().map(|song| song.duration).unwrap_or(0.0);
+            status.position = 0.0;
+        });
+
+        self.preload_next();
+    }
+
+    /// Move the queue on after the chain ran out by itself
+    fn advance(&mut self, sink: &Sink) -> Result<(), String> {
+        self.chain = None;
+
+        {
+            let mut queue = self.queue.blocking_lock();
+            queue.advance();
+            tauri::async_runtime::block_on(save_queue(&self.db_pool, &queue, 0.0))
+                .map_err(|e| e.to_string())?;
+            let _ = self.app_handle.emit("queue_changed", queue.snapshot());
+        }
+
+        self.play_current(sink, 0.0, false)
+    }
+
+    /// Fade the output to silence if anything is audible. Returns whether it did.
+    fn fade_out(&self) -> bool {
+        let Some(chain) = &self.chain else {
+            return false;
+        };
+        if self.status.blocking_read().state != PlaybackState::Playing {
+            return false;
+        }
+
+        chain.fade_to(0.0, CLICK_FADE);
+        thread::sleep(CLICK_FADE + Duration::from_millis(10));
+        true
+    }
+
+    fn position(&self) -> f32 {
+        self.chain
+            .as_ref()
+            .map(|chain| chain.position().as_secs_f32())
+            .unwrap_or(0.0)
+    }
+
+    fn update_status(&self, update: impl FnOnce(&mut PlayerStatus)) {
+        let mut status = self.status.blocking_write();
+        update(&mut status);
+        let _ = self.app_handle.emit("player_state", status.clone());
+    }
+
+    fn save_position(&self, position: f32) {
+        if let Err(e) = tauri::async_runtime::block_on(save_position(&self.db_pool, position)) {
+            eprintln!("Failed to save playback position: {}", e);
+        }
+    }
+}
+
+/// Linear gain that normalizes `song` under `mode`, lowered where needed so
+/// its peak doesn't clip. Album mode falls back to track gain when an album
+/// value is missing, and to the track peak when only the album peak is.
+fn normalization_gain(song: &SongInfo, mode: NormalizationMode) -> f32 {
+    let (gain, peak) = match mode {
+        NormalizationMode::Off => return 1.0,
+        NormalizationMode::Track => (song.replaygain_track_gain, song.replaygain_track_peak),
+        NormalizationMode::Album => match song.replaygain_album_gain {
+            Some(gain) => (
+                Some(gain),
+                song.replaygain_album_peak.or(song.replaygain_track_peak),
+            ),
+            None => (song.replaygain_track_gain, song.replaygain_track_peak),
+        },
+    };
+
+    let Some(gain) = gain else {
+        return 1.0;
+    };
+    let factor = 10f32.powf(gain / 20.0);
+    match peak {
+        Some(peak) if peak > 0.0 => factor.min(1.0 / peak),
+        _ => factor,
+    }
+}
+
+/// Whether `next` carries straight on from `current` on the same album, in
+/// which case the two are joined gaplessly rather than crossfaded
+fn is_continuation(current: &SongInfo, next: &SongInfo) -> bool {
+    let track_number = |song: &SongInfo| -> Option<u32> {
+        let number = song.track_number.as_deref()?;
+        let digits: String = number
+            .trim()
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    };
+
+    current.album_id.is_some()
+        && current.album_id == next.album_id
+        && matches!(
+            (track_number(current), track_number(next)),
+            (Some(a), Some(b)) if a.checked_add(1) == Some(b)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(track: (Option<f32>, Option<f32>), album: (Option<f32>, Option<f32>)) -> SongInfo {
+        SongInfo {
+            replaygain_track_gain: track.0,
+            replaygain_track_peak: track.1,
+            replaygain_album_gain: album.0,
+            replaygain_album_peak: album.1,
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn off_leaves_the_signal_alone() {
+        let song = song((Some(-6.0), Some(0.5)), (Some(-6.0), Some(0.5)));
+        assert_eq!(normalization_gain(&song, NormalizationMode::Off), 1.0);
+    }
+
+    #[test]
+    fn gain_is_limited_by_the_peak() {
+        // +6 dB would push a 0.8 peak past full scale
+        let song = song((Some(6.0), Some(0.8)), (None, None));
+        assert_close(normalization_gain(&song, NormalizationMode::Track), 1.25);
+    }
+
+    #[test]
+    fn album_mode_falls_back_to_track_values() {
+        let song = song((Some(-6.0), Some(0.5)), (None, None));
+        assert_close(
+            normalization_gain(&song, NormalizationMode::Album),
+            10f32.powf(-6.0 / 20.0),
+        );
+    }
+
+    #[test]
+    fn album_gain_without_album_peak_uses_the_track_peak() {
+        let song = song((Some(-3.0), Some(0.9)), (Some(6.0), None));
+        assert_close(
+            normalization_gain(&song, NormalizationMode::Album),
+            1.0 / 0.9,
+        );
+    }
+
+    #[test]
+    fn missing_gain_plays_at_unity() {
+        let song = song((None, Some(0.5)), (None, Some(0.5)));
+        assert_eq!(normalization_gain(&song, NormalizationMode::Track), 1.0);
+        assert_eq!(normalization_gain(&song, NormalizationMode::Album), 1.0);
+    }
+}
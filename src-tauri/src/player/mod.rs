@@ -0,0 +1,4 @@
+pub mod chain;
+pub mod decoder;
+pub mod engine;
+pub mod queue;
@@ -0,0 +1,584 @@
+use crate::models::{QueueSnapshot, RepeatMode, SongInfo};
+use rand::seq::SliceRandom;
+use sqlx::{Row, SqlitePool};
+
+/// The play queue. `tracks` keeps the order songs were enqueued in, while `order`
+/// is the order they are actually played in. Shuffling only permutes `order`, so
+/// turning shuffle off again restores the original sequence.
+#[derive(Default)]
+pub struct PlayQueue {
+    tracks: Vec<SongInfo>,
+    order: Vec<usize>,
+    current: Option<usize>,
+    repeat: RepeatMode,
+    shuffled: bool,
+}
+
+impl PlayQueue {
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn current(&self) -> Option<&SongInfo> {
+        self.current.map(|index| &self.tracks[self.order[index]])
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SongInfo> {
+        self.order.get(index).map(|&track| &self.tracks[track])
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_repeat_mode(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    /// Append songs to the end of the queue
+    pub fn enqueue(&mut self, songs: Vec<SongInfo>) {
+        for song in songs {
+            self.order.push(self.tracks.len());
+            self.tracks.push(song);
+        }
+    }
+
+    /// Insert a song right after the current one and return its queue index
+    pub fn insert_next(&mut self, song: SongInfo) -> usize {
+        let index = self.current.map(|c| c + 1).unwrap_or(self.order.len());
+
+        if self.shuffled {
+            self.order.insert(index, self.tracks.len());
+            self.tracks.push(song);
+        } else {
+            self.tracks.insert(index, song);
+            self.order = (0..self.tracks.len()).collect();
+        }
+
+        index
+    }
+
+    /// Find the queue index of a song
+    pub fn position_of(&self, song_id: i64) -> Option<usize> {
+        self.order
+            .iter()
+            .position(|&track| self.tracks[track].id == Some(song_id))
+    }
+
+    /// Make the entry at `index` the current one
+    pub fn jump(&mut self, index: usize) -> Option<&SongInfo> {
+        if index >= self.order.len() {
+            return None;
+        }
+        self.current = Some(index);
+        self.current()
+    }
+
+    /// Move to the track that should play once the current one finishes on its own.
+    /// Repeat-one keeps the current track.
+    pub fn advance(&mut self) -> Option<&SongInfo> {
+        if self.repeat == RepeatMode::One {
+            return self.current();
+        }
+        self.skip_forward()
+    }
+
+    /// Index of the track `advance` would move to, without moving
+    pub fn peek_advance(&self) -> Option<usize> {
+        match self.repeat {
+            RepeatMode::One => self.current,
+            _ => self.next_index(),
+        }
+    }
+
+    /// Skip to the next track. Wraps around only in repeat-all mode.
+    pub fn skip_forward(&mut self) -> Option<&SongInfo> {
+        self.current = self.next_index();
+        self.current()
+    }
+
+    /// Go back to the previous track. Wraps around only in repeat-all mode.
+    pub fn skip_back(&mut self) -> Option<&SongInfo> {
+        self.current = match self.current {
+            Some(0) if self.repeat == RepeatMode::All => self.order.len().checked_sub(1),
+            Some(0) => Some(0),
+            Some(index) => Some(index - 1),
+            None => None,
+        };
+        self.current()
+    }
+
+    fn next_index(&self) -> Option<usize> {
+        let index = self.current?;
+        if index + 1 < self.order.len() {
+            Some(index + 1)
+        } else if self.repeat == RepeatMode::All {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Remove the entry at `index`. Returns true if it was the current track.
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.order.len() {
+            return false;
+        }
+
+        let track = self.order.remove(index);
+        self.tracks.remove(track);
+        for entry in self.order.iter_mut() {
+            if *entry > track {
+                *entry -= 1;
+            }
+        }
+
+        match self.current {
+            Some(current) if current == index => {
+                self.current = (index < self.order.len()).then_some(index);
+                true
+            }
+            Some(current) if current > index => {
+                self.current = Some(current - 1);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Move the entry at `from` so that it ends up at `to`
+    pub fn move_entry(&mut self, from: usize, to: usize) {
+        let len = self.order.len();
+        if from >= len || to >= len || from == to {
+            return;
+        }
+
+        if self.shuffled {
+            let track = self.order.remove(from);
+            self.order.insert(to, track);
+        } else {
+            let track = self.tracks.remove(from);
+            self.tracks.insert(to, track);
+        }
+
+        self.current = self.current.map(|current| {
+            if current == from {
+                to
+            } else if from < current && current <= to {
+                current - 1
+            } else if to <= current && current < from {
+                current + 1
+            } else {
+                current
+            }
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.order.clear();
+        self.current = None;
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffled
+    }
+
+    /// Turn shuffle on or off. The current track stays current either way; when
+    /// shuffling it is moved to the front so everything else is still ahead of it.
+    pub fn set_shuffle(&mut self, enabled: bool) {
+        if enabled == self.shuffled {
+            return;
+        }
+
+        let current_track = self.current.map(|index| self.order[index]);
+        self.order = (0..self.tracks.len()).collect();
+
+        if enabled {
+            let mut rng = rand::thread_rng();
+            match current_track {
+                Some(track) => {
+                    self.order.retain(|&t| t != track);
+                    self.order.shuffle(&mut rng);
+                    self.order.insert(0, track);
+                    self.current = Some(0);
+                }
+                None => self.order.shuffle(&mut rng),
+            }
+        } else {
+            self.current = current_track;
+        }
+
+        self.shuffled = enabled;
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            tracks: self
+                .order
+                .iter()
+                .map(|&track| self.tracks[track].clone())
+                .collect(),
+            current_index: self.current,
+            repeat_mode: self.repeat,
+            shuffled: self.shuffled,
+        }
+    }
+}
+
+/// Load the queue saved by `save_queue`, along with the saved playback position
+pub async fn load_queue(db_pool: &SqlitePool) -> Result<(PlayQueue, f32), sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            q.position AS queue_position, q.play_order,
+            s.id, s.album_id, s.title, s.artist, s.album, s.genre, s.duration, s.path,
+            s.lyrics_path, s.album_artist, s.year, s.label, s.track_number, s.format, s.codec,
+            s.replaygain_track_gain, s.replaygain_track_peak,
+            s.replaygain_album_gain, s.replaygain_album_peak
+        FROM queue_entries q
+        JOIN songs s ON s.id = q.song_id
+        ORDER BY q.position
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let state = sqlx::query(
+        "SELECT current_entry, position, repeat_mode, shuffled FROM queue_state WHERE id = 1",
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    let mut queue = PlayQueue::default();
+    let mut play_orders = Vec::with_capacity(rows.len());
+    let mut saved_positions = Vec::with_capacity(rows.len());
+
+    for row in &rows {
+        saved_positions.push(row.get::<i64, _>("queue_position"));
+        play_orders.push(row.get::<i64, _>("play_order"));
+        queue.tracks.push(SongInfo::from_row(row));
+    }
+
+    // Songs removed from the library since the queue was saved leave gaps, so
+    // rebuild the play order from the relative order of what is left.
+    let mut order: Vec<usize> = (0..queue.tracks.len()).collect();
+    order.sort_by_key(|&track| play_orders[track]);
+    queue.order = order;
+
+    let mut position = 0.0;
+    if let Some(state) = state {
+        let current_entry: Option<i64> = state.get("current_entry");
+        queue.current = current_entry
+            .and_then(|entry| saved_positions.iter().position(|&p| p == entry))
+            .and_then(|track| queue.order.iter().position(|&t| t == track));
+        queue.repeat = RepeatMode::parse(state.get("repeat_mode"));
+        queue.shuffled = state.get("shuffled");
+        if queue.current.is_some() {
+            position = state.get("position");
+        }
+    }
+
+    Ok((queue, position))
+}
+
+/// Persist the whole queue, replacing whatever was saved before
+pub async fn save_queue(
+    db_pool: &SqlitePool,
+    queue: &PlayQueue,
+    position: f32,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    sqlx::query("DELETE FROM queue_entries")
+        .execute(&mut *tx)
+        .await?;
+
+    for (play_order, &track) in queue.order.iter().enumerate() {
+        sqlx::query("INSERT INTO queue_entries (position, song_id, play_order) VALUES (?, ?, ?)")
+            .bind(track as i64)
+            .bind(queue.tracks[track].id)
+            .bind(play_order as i64)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT OR REPLACE INTO queue_state (id, current_entry, position, repeat_mode, shuffled)
+        VALUES (1, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(queue.current.map(|index| queue.order[index] as i64))
+    .bind(position)
+    .bind(queue.repeat.as_str())
+    .bind(queue.shuffled)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Persist only the playback position within the current track
+pub async fn save_position(db_pool: &SqlitePool, position: f32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE queue_state SET position = ? WHERE id = 1")
+        .bind(position)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(titles: &[&str]) -> PlayQueue {
+        let mut queue = PlayQueue::default();
+        queue.enqueue(titles.iter().map(|title| song(title)).collect());
+        queue
+    }
+
+    fn song(title: &str) -> SongInfo {
+        SongInfo {
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Titles in play order
+    fn titles(queue: &PlayQueue) -> Vec<String> {
+        (0..queue.len())
+            .map(|index| queue.get(index).unwrap().title.clone())
+            .collect()
+    }
+
+    fn current(queue: &PlayQueue) -> Option<String> {
+        queue.current().map(|song| song.title.clone())
+    }
+
+    /// A shuffled queue of six songs playing the one at `index`
+    fn shuffled_at(index: usize) -> PlayQueue {
+        let mut queue = queue(&["A", "B", "C", "D", "E", "F"]);
+        queue.jump(0);
+        queue.set_shuffle(true);
+        queue.jump(index);
+        queue
+    }
+
+    #[test]
+    fn insert_next_plays_after_the_current_song() {
+        let mut queue = queue(&["A", "B", "C", "D"]);
+        queue.jump(1);
+
+        assert_eq!(queue.insert_next(song("X")), 2);
+        assert_eq!(titles(&queue), ["A", "B", "X", "C", "D"]);
+        assert_eq!(queue.current_index(), Some(1));
+        assert_eq!(current(&queue).as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn insert_next_without_a_current_song_appends() {
+        let mut queue = queue(&["A", "B"]);
+
+        assert_eq!(queue.insert_next(song("X")), 2);
+        assert_eq!(titles(&queue), ["A", "B", "X"]);
+        assert_eq!(queue.current_index(), None);
+    }
+
+    #[test]
+    fn insert_next_while_shuffled() {
+        let mut queue = shuffled_at(2);
+        let playing = current(&queue);
+        let mut expected = titles(&queue);
+
+        assert_eq!(queue.insert_next(song("X")), 3);
+        expected.insert(3, "X".to_string());
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(queue.current_index(), Some(2));
+        assert_eq!(current(&queue), playing);
+
+        // Unshuffled, the song takes its place in the order it was added
+        queue.set_shuffle(false);
+        assert_eq!(titles(&queue), ["A", "B", "C", "D", "E", "F", "X"]);
+        assert_eq!(current(&queue), playing);
+    }
+
+    #[test]
+    fn move_entry_keeps_the_current_song() {
+        let mut queue = queue(&["A", "B", "C", "D", "E"]);
+        queue.jump(2);
+
+        // From before the current song to after it
+        queue.move_entry(0, 3);
+        assert_eq!(titles(&queue), ["B", "C", "D", "A", "E"]);
+        assert_eq!(queue.current_index(), Some(1));
+
+        // From after it to before it
+        queue.move_entry(4, 0);
+        assert_eq!(titles(&queue), ["E", "B", "C", "D", "A"]);
+        assert_eq!(queue.current_index(), Some(2));
+
+        // The current song itself
+        queue.move_entry(2, 4);
+        assert_eq!(titles(&queue), ["E", "B", "D", "A", "C"]);
+        assert_eq!(queue.current_index(), Some(4));
+        assert_eq!(current(&queue).as_deref(), Some("C"));
+
+        // Out of range
+        queue.move_entry(1, 5);
+        assert_eq!(titles(&queue), ["E", "B", "D", "A", "C"]);
+    }
+
+    #[test]
+    fn move_entry_while_shuffled() {
+        let mut queue = shuffled_at(3);
+        let playing = current(&queue);
+        let mut expected = titles(&queue);
+
+        // Both ends before the current song
+        queue.move_entry(0, 1);
+        expected.swap(0, 1);
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(queue.current_index(), Some(3));
+
+        // From before the current song to after it
+        queue.move_entry(1, 4);
+        let moved = expected.remove(1);
+        expected.insert(4, moved);
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(queue.current_index(), Some(2));
+        assert_eq!(current(&queue), playing);
+
+        // The current song itself
+        queue.move_entry(2, 0);
+        let moved = expected.remove(2);
+        expected.insert(0, moved);
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(queue.current_index(), Some(0));
+        assert_eq!(current(&queue), playing);
+
+        // Moves only change the shuffled order
+        queue.set_shuffle(false);
+        assert_eq!(titles(&queue), ["A", "B", "C", "D", "E", "F"]);
+        assert_eq!(current(&queue), playing);
+    }
+
+    #[test]
+    fn remove_keeps_the_current_song() {
+        let mut queue = queue(&["A", "B", "C", "D"]);
+        queue.jump(2);
+
+        assert!(!queue.remove(0));
+        assert_eq!(titles(&queue), ["B", "C", "D"]);
+        assert_eq!(queue.current_index(), Some(1));
+        assert_eq!(current(&queue).as_deref(), Some("C"));
+
+        assert!(!queue.remove(2));
+        assert_eq!(titles(&queue), ["B", "C"]);
+        assert_eq!(queue.current_index(), Some(1));
+
+        assert!(!queue.remove(5));
+        assert_eq!(titles(&queue), ["B", "C"]);
+    }
+
+    #[test]
+    fn removing_the_current_song_moves_on() {
+        let mut queue = queue(&["A", "B", "C"]);
+        queue.jump(1);
+
+        assert!(queue.remove(1));
+        assert_eq!(titles(&queue), ["A", "C"]);
+        assert_eq!(current(&queue).as_deref(), Some("C"));
+
+        // Nothing follows the last song
+        assert!(queue.remove(1));
+        assert_eq!(titles(&queue), ["A"]);
+        assert_eq!(queue.current_index(), None);
+    }
+
+    #[test]
+    fn remove_while_shuffled() {
+        let mut queue = shuffled_at(2);
+        let playing = current(&queue);
+        let mut expected = titles(&queue);
+
+        // Before the current song
+        assert!(!queue.remove(0));
+        let removed = expected.remove(0);
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(queue.current_index(), Some(1));
+        assert_eq!(current(&queue), playing);
+
+        // After it
+        assert!(!queue.remove(3));
+        let also_removed = expected.remove(3);
+        assert_eq!(titles(&queue), expected);
+        assert_eq!(current(&queue), playing);
+
+        // Unshuffled, the rest keep the order they were added in
+        queue.set_shuffle(false);
+        let remaining: Vec<&str> = ["A", "B", "C", "D", "E", "F"]
+            .into_iter()
+            .filter(|title| *title != removed && *title != also_removed)
+            .collect();
+        assert_eq!(titles(&queue), remaining);
+        assert_eq!(current(&queue), playing);
+    }
+
+    #[test]
+    fn removing_the_current_song_while_shuffled_moves_on() {
+        let mut queue = shuffled_at(2);
+        let next = queue.get(3).unwrap().title.clone();
+
+        assert!(queue.remove(2));
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.current_index(), Some(2));
+        assert_eq!(current(&queue), Some(next));
+    }
+
+    #[test]
+    fn shuffle_puts_the_current_song_first() {
+        let mut queue = queue(&["A", "B", "C", "D", "E", "F"]);
+        queue.jump(3);
+
+        queue.set_shuffle(true);
+        assert!(queue.is_shuffled());
+        assert_eq!(queue.current_index(), Some(0));
+        assert_eq!(current(&queue).as_deref(), Some("D"));
+        let mut shuffled = titles(&queue);
+        shuffled.sort();
+        assert_eq!(shuffled, ["A", "B", "C", "D", "E", "F"]);
+
+        // Turning it on again doesn't reshuffle
+        let order = titles(&queue);
+        queue.set_shuffle(true);
+        assert_eq!(titles(&queue), order);
+
+        queue.set_shuffle(false);
+        assert!(!queue.is_shuffled());
+        assert_eq!(titles(&queue), ["A", "B", "C", "D", "E", "F"]);
+        assert_eq!(queue.current_index(), Some(3));
+        assert_eq!(current(&queue).as_deref(), Some("D"));
+    }
+
+    #[test]
+    fn shuffle_without_a_current_song() {
+        let mut queue = queue(&["A", "B", "C"]);
+
+        queue.set_shuffle(true);
+        assert_eq!(queue.current_index(), None);
+        assert_eq!(queue.len(), 3);
+
+        queue.set_shuffle(false);
+        assert_eq!(titles(&queue), ["A", "B", "C"]);
+        assert_eq!(queue.current_index(), None);
+    }
+}
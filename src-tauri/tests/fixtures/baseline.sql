@@ -0,0 +1,48 @@
+-- A library from the first release: no library roots, file sizes, formats or
+-- ReplayGain columns, and no queue tables
+
+CREATE TABLE IF NOT EXISTS albums (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    year TEXT,
+    genre TEXT,
+    cover_art_base64 TEXT,
+    song_count INTEGER DEFAULT 0,
+    total_duration REAL DEFAULT 0.0,
+    folder_path TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(title, artist, folder_path)
+);
+
+CREATE TABLE IF NOT EXISTS songs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    album_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    album TEXT NOT NULL,
+    genre TEXT,
+    duration REAL DEFAULT 0.0,
+    path TEXT NOT NULL UNIQUE,
+    lyrics_path TEXT,
+    album_artist TEXT,
+    year TEXT,
+    label TEXT,
+    track_number TEXT,
+    file_modified_time INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (album_id) REFERENCES albums (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_albums_artist_title ON albums(artist, title);
+CREATE INDEX IF NOT EXISTS idx_songs_album_id ON songs(album_id);
+CREATE INDEX IF NOT EXISTS idx_songs_path ON songs(path);
+
+INSERT INTO albums (id, title, artist, year, song_count, total_duration, folder_path)
+VALUES (1, 'Kind of Blue', 'Miles Davis', '1959', 1, 562.0, '/music/Kind of Blue');
+
+INSERT INTO songs (id, album_id, title, artist, album, duration, path, track_number)
+VALUES (1, 1, 'So What', 'Miles Davis', 'Kind of Blue', 562.0,
+        '/music/Kind of Blue/01 So What.mp3', '1');
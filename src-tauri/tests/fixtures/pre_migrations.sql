@@ -0,0 +1,115 @@
+-- A library as created by the hand-rolled schema code, just before versioned
+-- migrations were introduced
+
+CREATE TABLE IF NOT EXISTS library_roots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL UNIQUE,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    watch BOOLEAN NOT NULL DEFAULT 1,
+    exclusions TEXT NOT NULL DEFAULT '[]',
+    online BOOLEAN NOT NULL DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS albums (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    root_id INTEGER REFERENCES library_roots (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    year TEXT,
+    genre TEXT,
+    cover_art_base64 TEXT,
+    song_count INTEGER DEFAULT 0,
+    total_duration REAL DEFAULT 0.0,
+    folder_path TEXT NOT NULL,
+    replaygain_album_gain REAL,
+    replaygain_album_peak REAL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(title, artist, folder_path)
+);
+
+CREATE TABLE IF NOT EXISTS songs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    album_id INTEGER NOT NULL,
+    root_id INTEGER REFERENCES library_roots (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    album TEXT NOT NULL,
+    genre TEXT,
+    duration REAL DEFAULT 0.0,
+    path TEXT NOT NULL UNIQUE,
+    lyrics_path TEXT,
+    album_artist TEXT,
+    year TEXT,
+    label TEXT,
+    track_number TEXT,
+    file_modified_time INTEGER,
+    file_size INTEGER,
+    format TEXT,
+    codec TEXT,
+    replaygain_track_gain REAL,
+    replaygain_track_peak REAL,
+    replaygain_album_gain REAL,
+    replaygain_album_peak REAL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (album_id) REFERENCES albums (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS scan_errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    root_id INTEGER REFERENCES library_roots (id) ON DELETE CASCADE,
+    path TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS queue_entries (
+    position INTEGER PRIMARY KEY,
+    song_id INTEGER NOT NULL,
+    play_order INTEGER NOT NULL,
+    FOREIGN KEY (song_id) REFERENCES songs (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS queue_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    current_entry INTEGER,
+    position REAL DEFAULT 0.0,
+    repeat_mode TEXT NOT NULL DEFAULT 'off',
+    shuffled BOOLEAN NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_albums_artist_title ON albums(artist, title);
+CREATE INDEX IF NOT EXISTS idx_songs_album_id ON songs(album_id);
+CREATE INDEX IF NOT EXISTS idx_songs_path ON songs(path);
+CREATE INDEX IF NOT EXISTS idx_songs_root_id ON songs(root_id);
+CREATE INDEX IF NOT EXISTS idx_albums_root_id ON albums(root_id);
+
+INSERT INTO library_roots (id, path) VALUES (1, '/music');
+
+INSERT INTO albums (
+    id, root_id, title, artist, year, cover_art_base64, song_count, total_duration, folder_path
+)
+VALUES (
+    1, 1, 'Blue Train', 'John Coltrane', '1957',
+    'iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=',
+    2, 1020.5, '/music/Blue Train'
+);
+
+INSERT INTO songs (
+    id, album_id, root_id, title, artist, album, genre, duration, path, track_number,
+    file_modified_time, file_size, format, codec, replaygain_track_gain
+) VALUES
+    (1, 1, 1, 'Blue Train', 'John Coltrane', 'Blue Train', 'Jazz', 643.0,
+     '/music/Blue Train/01 Blue Train.flac', '1', 1700000000, 61234567, 'FLAC', 'FLAC', -3.5),
+    (2, 1, 1, 'Moment''s Notice', 'John Coltrane', 'Blue Train', 'jazz ', 377.5,
+     '/music/Blue Train/02 Moment''s Notice.flac', '2', 1700000000, 36234567, 'FLAC', 'FLAC', NULL);
+
+INSERT INTO scan_errors (root_id, path, kind, message)
+VALUES (1, '/music/broken.mp3', 'corrupt', 'Invalid frame header');
+
+INSERT INTO queue_entries (position, song_id, play_order) VALUES (0, 2, 0), (1, 1, 1);
+INSERT INTO queue_state (id, current_entry, position, repeat_mode, shuffled)
+VALUES (1, 0, 12.5, 'all', 0);
@@ -0,0 +1,217 @@
+use musicthing_lib::metadata::covers::CoverCache;
+use musicthing_lib::metadata::scanner::initialize_database;
+use sqlx::{Row, SqlitePool};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A fresh directory for one test's database
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "musicthing-migrations-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Create a database at `db_path` from one of the SQL fixtures
+async fn load_fixture(db_path: &Path, fixture: &str) {
+    let sql = fs::read_to_string(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(fixture),
+    )
+    .unwrap();
+
+    let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", db_path.display()))
+        .await
+        .unwrap();
+    sqlx::raw_sql(&sql).execute(&pool).await.unwrap();
+    pool.close().await;
+}
+
+async fn applied_versions(pool: &SqlitePool) -> Vec<i64> {
+    sqlx::query("SELECT version FROM _sqlx_migrations WHERE success = 1 ORDER BY version")
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.get("version"))
+        .collect()
+}
+
+#[tokio::test]
+async fn upgrades_database_from_before_migrations() {
+    let dir = test_dir("pre-migrations");
+    let db_path = dir.join("music.db");
+    load_fixture(&db_path, "pre_migrations.sql").await;
+
+    let pool = initialize_database(&db_path, &CoverCache::new(dir.join("covers")))
+        .await
+        .unwrap();
+
+    assert_eq!(applied_versions(&pool).await, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(dir.join("music.db.v0.bak").exists());
+
+    let songs = sqlx::query("SELECT title, root_id, codec FROM songs ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(songs.len(), 2);
+    assert_eq!(songs[1].get::<String, _>("title"), "Moment's Notice");
+    assert_eq!(songs[0].get::<Option<i64>, _>("root_id"), Some(1));
+    assert_eq!(
+        songs[0].get::<Option<String>, _>("codec").as_deref(),
+        Some("FLAC")
+    );
+
+    // The inline cover moved into the cache
+    let cover = sqlx::query(
+        "SELECT covers.hash, covers.mime_type FROM albums JOIN covers ON covers.id = albums.cover_id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(cover.get::<String, _>("mime_type"), "image/png");
+    let hash: String = cover.get("hash");
+    assert!(dir.join("covers").join(&hash[..2]).join(&hash).exists());
+
+    let inline_covers =
+        sqlx::query("SELECT 1 FROM pragma_table_info('albums') WHERE name = 'cover_art_base64'")
+            .fetch_optional(&pool)
+            .await
+            .unwrap();
+    assert!(inline_covers.is_none());
+
+    // Artists were filled in from the song and album columns
+    let artist = sqlx::query(
+        r#"
+        SELECT artists.name, COUNT(*) AS songs
+        FROM artists JOIN song_artists ON song_artists.artist_id = artists.id
+        GROUP BY artists.id
+        "#,
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(artist.get::<String, _>("name"), "John Coltrane");
+    assert_eq!(artist.get::<i64, _>("songs"), 2);
+
+    // Genres too, spellings differing in case or spaces becoming one genre
+    let genre = sqlx::query(
+        r#"
+        SELECT genres.name, COUNT(*) AS songs
+        FROM genres JOIN song_genres ON song_genres.genre_id = genres.id
+        GROUP BY genres.id
+        "#,
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(genre.get::<String, _>("name"), "Jazz");
+    assert_eq!(genre.get::<i64, _>("songs"), 2);
+
+    // The search indexes cover what was already in the library
+    let indexed: Vec<String> = sqlx::query(
+        r#"
+        SELECT 'album' AS kind FROM album_search WHERE album_search MATCH 'coltrane'
+        UNION ALL
+        SELECT 'artist' FROM artist_search WHERE artist_search MATCH 'coltrane'
+        UNION ALL
+        SELECT 'song' FROM song_search WHERE song_search MATCH 'coltrane'
+        "#,
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap()
+    .iter()
+    .map(|row| row.get("kind"))
+    .collect();
+    assert_eq!(indexed, vec!["album", "artist", "song", "song"]);
+
+    // Every file is read again on the next scan
+    let unstamped: i64 =
+        sqlx::query("SELECT COUNT(*) AS count FROM songs WHERE file_modified_time IS NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .get("count");
+    assert_eq!(unstamped, 2);
+
+    let queued: i64 = sqlx::query("SELECT COUNT(*) AS count FROM queue_entries")
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+        .get("count");
+    assert_eq!(queued, 2);
+
+    pool.close().await;
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn upgrades_database_from_first_release() {
+    let dir = test_dir("first-release");
+    let db_path = dir.join("music.db");
+    load_fixture(&db_path, "baseline.sql").await;
+
+    let pool = initialize_database(&db_path, &CoverCache::new(dir.join("covers")))
+        .await
+        .unwrap();
+
+    assert_eq!(applied_versions(&pool).await, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(dir.join("music.db.v0.bak").exists());
+
+    let song = sqlx::query(
+        "SELECT title, root_id, file_size, format, replaygain_track_gain FROM songs WHERE id = 1",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(song.get::<String, _>("title"), "So What");
+    assert_eq!(song.get::<Option<i64>, _>("root_id"), None);
+    assert_eq!(song.get::<Option<String>, _>("format"), None);
+
+    // Tables added after the first release exist and work
+    sqlx::query("INSERT INTO library_roots (path) VALUES ('/music')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO queue_state (id) VALUES (1)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    pool.close().await;
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn new_database_is_not_backed_up() {
+    let dir = test_dir("fresh");
+    let db_path = dir.join("music.db");
+
+    let pool = initialize_database(&db_path, &CoverCache::new(dir.join("covers")))
+        .await
+        .unwrap();
+    assert_eq!(applied_versions(&pool).await, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    pool.close().await;
+
+    // Opening it again has nothing left to migrate
+    let pool = initialize_database(&db_path, &CoverCache::new(dir.join("covers")))
+        .await
+        .unwrap();
+    assert_eq!(applied_versions(&pool).await, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    pool.close().await;
+
+    let backups = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".bak"))
+        .count();
+    assert_eq!(backups, 0);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
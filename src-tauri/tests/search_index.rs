@@ -0,0 +1,96 @@
+use musicthing_lib::metadata::covers::CoverCache;
+use musicthing_lib::metadata::scanner::initialize_database;
+use sqlx::{Row, SqlitePool};
+use std::fs;
+use std::time::{Duration, Instant};
+
+const SONGS: i64 = 40_000;
+
+/// A fresh database with one album of `SONGS` indexed songs
+async fn indexed_library(name: &str) -> (SqlitePool, std::path::PathBuf) {
+    let dir =
+        std::env::temp_dir().join(format!("musicthing-search-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let pool = initialize_database(&dir.join("music.db"), &CoverCache::new(dir.join("covers")))
+        .await
+        .unwrap();
+
+    sqlx::raw_sql(&format!(
+        r#"
+        INSERT INTO albums (id, title, artist, folder_path) VALUES (1, 'Album', 'Artist', '/music');
+
+        WITH RECURSIVE n (i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < {})
+        INSERT INTO songs (id, album_id, title, artist, album, path)
+        SELECT i, 1, 'Song ' || i, 'Artist ' || (i % 500), 'Album', '/music/' || i || '.flac'
+        FROM n;
+
+        INSERT INTO song_search (rowid, title, artist, album, lyrics)
+        SELECT id, title, artist, album, 'some words of lyrics for song ' || id FROM songs;
+        "#,
+        SONGS
+    ))
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    (pool, dir)
+}
+
+async fn count(pool: &SqlitePool, sql: &str) -> i64 {
+    sqlx::query(sql).fetch_one(pool).await.unwrap().get(0)
+}
+
+#[tokio::test]
+async fn deleting_songs_removes_their_index_rows_quickly() {
+    let (pool, dir) = indexed_library("bulk-delete").await;
+
+    let started = Instant::now();
+    sqlx::query("DELETE FROM songs WHERE id <= 1000")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let elapsed = started.elapsed();
+
+    // A full scan of the index per deleted song takes tens of seconds here
+    assert!(
+        elapsed < Duration::from_secs(2),
+        "deleting 1000 songs took {:?}",
+        elapsed
+    );
+    assert_eq!(
+        count(&pool, "SELECT COUNT(*) FROM song_search").await,
+        SONGS - 1000
+    );
+    assert_eq!(
+        count(
+            &pool,
+            "SELECT COUNT(*) FROM song_search WHERE song_search MATCH 'lyrics' AND rowid <= 1000"
+        )
+        .await,
+        0
+    );
+
+    pool.close().await;
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn deleting_an_album_removes_its_index_row() {
+    let (pool, dir) = indexed_library("album-delete").await;
+    sqlx::query("INSERT INTO album_search (rowid, title) VALUES (1, 'Album')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    sqlx::query("DELETE FROM albums WHERE id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(count(&pool, "SELECT COUNT(*) FROM album_search").await, 0);
+
+    pool.close().await;
+    fs::remove_dir_all(&dir).unwrap();
+}
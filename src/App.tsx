@@ -2,22 +2,57 @@ import React, { useState, useEffect, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import { listen } from "@tauri-apps/api/event";
+import { errorMessage, hasErrorCode } from "./errors";
 import Welcome from "./components/Welcome";
 import Header from "./components/Header";
 import MusicLibrary from "./components/MusicLibrary";
 import AlbumView from "./components/AlbumView";
-import { Album, SongInfo } from "./types";
+import ArtistList from "./components/ArtistList";
+import ArtistView from "./components/ArtistView";
+import GenreList from "./components/GenreList";
+import GenreView from "./components/GenreView";
+import {
+  Album,
+  AlbumArtwork,
+  Artist,
+  Genre,
+  LibraryRoot,
+  ScanError,
+  ScanFailure,
+  ScanProgress,
+  SearchResults,
+  SongInfo,
+} from "./types";
 import "./App.css";
 
+/** Thumbnail size for the 200px covers in album grids */
+const GRID_COVER_SIZE = 256;
+
+/** Thumbnail size for the secondary artwork strip in the album view */
+const ARTWORK_SIZE = 256;
+
+/** Searches with a `field:` term, such as `year:1995..2000`, are structured queries */
+const STRUCTURED_QUERY = /(^|\s)-?[a-z_]+:/i;
+
 function App() {
-  const [musicFolderPath, setMusicFolderPath] = useState<string | null>(null);
+  const [libraryRoots, setLibraryRoots] = useState<LibraryRoot[] | null>(null);
   const [isScanning, setIsScanning] = useState<boolean>(false);
-  const [scanProgress, setScanProgress] = useState<number>(0);
+  const [scanProgress, setScanProgress] = useState<ScanProgress | null>(null);
   const [albums, setAlbums] = useState<Album[]>([]);
   const [selectedAlbum, setSelectedAlbum] = useState<Album | null>(null);
   const [albumSongs, setAlbumSongs] = useState<SongInfo[]>([]);
+  const [albumArtwork, setAlbumArtwork] = useState<AlbumArtwork[]>([]);
+  const [libraryView, setLibraryView] = useState<"albums" | "artists" | "genres">("albums");
+  const [artists, setArtists] = useState<Artist[]>([]);
+  const [selectedArtist, setSelectedArtist] = useState<Artist | null>(null);
+  const [artistAlbums, setArtistAlbums] = useState<Album[]>([]);
+  const [artistTracks, setArtistTracks] = useState<SongInfo[]>([]);
+  const [genres, setGenres] = useState<Genre[]>([]);
+  const [selectedGenre, setSelectedGenre] = useState<Genre | null>(null);
+  const [genreAlbums, setGenreAlbums] = useState<Album[]>([]);
   const [searchQuery, setSearchQuery] = useState<string>("");
-  const [searchResults, setSearchResults] = useState<Album[]>([]);
+  const [searchResults, setSearchResults] = useState<SearchResults | null>(null);
+  const [queryError, setQueryError] = useState<string | null>(null);
   const [showNotification, setShowNotification] = useState(false);
   const [notificationMessage, setNotificationMessage] = useState("");
 
@@ -31,49 +66,82 @@ function App() {
     return () => clearTimeout(timer);
   }, []);
 
+  const fetchLibraryRoots = useCallback(async () => {
+    try {
+      const roots: LibraryRoot[] = await invoke("get_library_roots");
+      setLibraryRoots(roots);
+      return roots;
+    } catch (error) {
+      console.error("Failed to get library folders:", error);
+      showTemporaryNotification(`Error fetching library folders: ${errorMessage(error)}`);
+      return [];
+    }
+  }, [showTemporaryNotification]);
+
   useEffect(() => {
-    const fetchFolderPath = async () => {
-      try {
-        const path: string | null = await invoke("get_music_folder_path");
-        setMusicFolderPath(path);
-        if (path) {
-          scanLibrary(path);
-        }
-      } catch (error) {
-        console.error("Failed to get music folder path:", error);
-        showTemporaryNotification(`Error fetching folder path: ${error}`);
+    const loadRoots = async () => {
+      const roots = await fetchLibraryRoots();
+      if (roots.length > 0) {
+        rescanLibrary();
       }
     };
-    fetchFolderPath();
-  }, [showTemporaryNotification]);
+    loadRoots();
+  }, [fetchLibraryRoots]);
 
   const fetchAlbums = useCallback(async () => {
     try {
       const fetchedAlbums: Album[] = await invoke("get_albums", {
         limit: 50,
         offset: 0,
+        coverSize: GRID_COVER_SIZE,
       });
       setAlbums(fetchedAlbums);
     } catch (error) {
       console.error("Failed to fetch albums:", error);
-      showTemporaryNotification(`Error fetching albums: ${error}`);
+      showTemporaryNotification(`Error fetching albums: ${errorMessage(error)}`);
     }
   }, [showTemporaryNotification]);
 
   useEffect(() => {
     let unlistenProgress: (() => void) | undefined;
     let unlistenComplete: (() => void) | undefined;
+    let unlistenCancelled: (() => void) | undefined;
+    let unlistenFailed: (() => void) | undefined;
 
     const setupListeners = async () => {
-      unlistenProgress = await listen<number>("scan_progress", (event) => {
+      unlistenProgress = await listen<ScanProgress>("scan_progress", (event) => {
         setScanProgress(event.payload);
       });
 
       unlistenComplete = await listen<void>("scan_complete", async () => {
         setIsScanning(false);
-        setScanProgress(100);
-        showTemporaryNotification("Music library scan complete!");
+        try {
+          const errors: ScanError[] = await invoke("get_scan_errors");
+          showTemporaryNotification(
+            errors.length > 0
+              ? `Music library scan complete, ${errors.length} files couldn't be added`
+              : "Music library scan complete!"
+          );
+        } catch (error) {
+          console.error("Failed to get scan errors:", error);
+          showTemporaryNotification("Music library scan complete!");
+        }
         await fetchAlbums();
+        await fetchLibraryRoots();
+      });
+
+      unlistenCancelled = await listen<void>("scan_cancelled", async () => {
+        setIsScanning(false);
+        showTemporaryNotification("Music library scan cancelled");
+        await fetchAlbums();
+        await fetchLibraryRoots();
+      });
+
+      unlistenFailed = await listen<ScanFailure>("scan_failed", (event) => {
+        console.error("Scan failed:", event.payload);
+        showTemporaryNotification(
+          `Scan of ${event.payload.path} failed: ${event.payload.message}`
+        );
       });
     };
 
@@ -82,17 +150,85 @@ function App() {
     return () => {
       if (unlistenProgress) unlistenProgress();
       if (unlistenComplete) unlistenComplete();
+      if (unlistenCancelled) unlistenCancelled();
+      if (unlistenFailed) unlistenFailed();
+    };
+  }, [showTemporaryNotification, fetchAlbums, fetchLibraryRoots]);
+
+  useEffect(() => {
+    let unlistenLibraryChanged: (() => void) | undefined;
+
+    const setupListener = async () => {
+      unlistenLibraryChanged = await listen<number[]>(
+        "library_changed",
+        async (event) => {
+          try {
+            const changed = new Map<number, Album | null>();
+            for (const albumId of event.payload) {
+              const album: Album | null = await invoke("get_album_by_id", {
+                albumId,
+                coverSize: GRID_COVER_SIZE,
+              });
+              changed.set(albumId, album);
+            }
+
+            const known = new Set(albums.map((album) => album.id));
+            const hasNewAlbums = [...changed].some(
+              ([albumId, album]) => album && !known.has(albumId)
+            );
+
+            if (hasNewAlbums) {
+              // New albums need their place in the sorted page
+              await fetchAlbums();
+            } else {
+              setAlbums((current) =>
+                current
+                  .filter((album) => changed.get(album.id) !== null)
+                  .map((album) => changed.get(album.id) ?? album)
+              );
+            }
+
+            if (selectedAlbum && changed.has(selectedAlbum.id)) {
+              const album: Album | null = await invoke("get_album_by_id", {
+                albumId: selectedAlbum.id,
+              });
+              if (album) {
+                setSelectedAlbum(album);
+                const songs: SongInfo[] = await invoke("get_album_songs", {
+                  albumId: album.id,
+                });
+                setAlbumSongs(songs);
+                const artwork: AlbumArtwork[] = await invoke("get_album_artwork", {
+                  albumId: album.id,
+                  coverSize: ARTWORK_SIZE,
+                });
+                setAlbumArtwork(artwork);
+              } else {
+                setSelectedAlbum(null);
+                setAlbumSongs([]);
+                setAlbumArtwork([]);
+              }
+            }
+          } catch (error) {
+            console.error("Failed to refresh changed albums:", error);
+          }
+        }
+      );
+    };
+
+    setupListener();
+
+    return () => {
+      if (unlistenLibraryChanged) unlistenLibraryChanged();
     };
-  }, [showTemporaryNotification, fetchAlbums]);
+  }, [albums, fetchAlbums, selectedAlbum]);
 
   useEffect(() => {
     const fetchScanStatus = async () => {
       try {
-        const [scanning, progress]: [boolean, number] = await invoke(
-          "get_scan_status"
-        );
-        setIsScanning(scanning);
-        setScanProgress(progress);
+        const status: ScanProgress = await invoke("get_scan_status");
+        setIsScanning(status.is_scanning);
+        setScanProgress(status);
       } catch (error) {
         console.error("Failed to get scan status:", error);
       }
@@ -107,71 +243,261 @@ function App() {
     fetchAlbums();
   }, [fetchAlbums]);
 
-  const selectMusicFolder = async () => {
+  const addMusicFolder = async () => {
     try {
       const selectedPath = await open({
         directory: true,
         multiple: false,
-        title: "Select Music Folder",
+        title: "Add Music Folder",
       });
       if (selectedPath && typeof selectedPath === "string") {
-        setMusicFolderPath(selectedPath);
-        await invoke("set_music_folder_path", { path: selectedPath });
-        showTemporaryNotification("Music folder path saved!");
-        scanLibrary(selectedPath);
+        const root: LibraryRoot = await invoke("add_library_root", {
+          path: selectedPath,
+        });
+        await fetchLibraryRoots();
+        showTemporaryNotification("Music folder added!");
+        scanRoots([root.id]);
       }
     } catch (error) {
-      console.error("Failed to select folder:", error);
-      showTemporaryNotification(`Error selecting folder: ${error}`);
+      console.error("Failed to add folder:", error);
+      showTemporaryNotification(`Error adding folder: ${errorMessage(error)}`);
+    }
+  };
+
+  const removeMusicFolder = async (root: LibraryRoot) => {
+    try {
+      await invoke("remove_library_root", { rootId: root.id });
+      await fetchLibraryRoots();
+      await fetchAlbums();
+      showTemporaryNotification(`Removed ${root.path}`);
+    } catch (error) {
+      console.error("Failed to remove folder:", error);
+      showTemporaryNotification(`Error removing folder: ${errorMessage(error)}`);
+    }
+  };
+
+  const updateRootOptions = async (root: LibraryRoot) => {
+    try {
+      await invoke("set_library_root_options", {
+        rootId: root.id,
+        enabled: root.enabled,
+        watch: root.watch,
+        exclusions: root.exclusions,
+      });
+      await fetchLibraryRoots();
+    } catch (error) {
+      console.error("Failed to update folder options:", error);
+      showTemporaryNotification(`Error updating folder: ${errorMessage(error)}`);
     }
   };
 
-  const scanLibrary = async (path: string) => {
+  const scanRoots = async (rootIds: number[]) => {
     setIsScanning(true);
-    setScanProgress(0);
+    setScanProgress(null);
     try {
-      await invoke("scan_music_library", { folderPath: path });
+      await invoke("scan_library_roots", { rootIds });
       showTemporaryNotification("Music library scan started!");
     } catch (error) {
       console.error("Failed to start scan:", error);
+      if (hasErrorCode(error, "scan_in_progress")) {
+        showTemporaryNotification("A scan is already running");
+        return;
+      }
       setIsScanning(false);
-      showTemporaryNotification(`Error starting scan: ${error}`);
+      showTemporaryNotification(`Error starting scan: ${errorMessage(error)}`);
     }
   };
 
   const rescanLibrary = async () => {
-    if (!musicFolderPath) {
-      showTemporaryNotification("No music folder path configured to rescan.");
-      return;
-    }
     setIsScanning(true);
-    setScanProgress(0);
+    setScanProgress(null);
     try {
       await invoke("rescan_library");
       showTemporaryNotification("Music library rescan started!");
     } catch (error) {
       console.error("Failed to rescan library:", error);
+      if (hasErrorCode(error, "scan_in_progress")) {
+        showTemporaryNotification("A scan is already running");
+        return;
+      }
       setIsScanning(false);
-      showTemporaryNotification(`Error rescanning library: ${error}`);
+      if (hasErrorCode(error, "no_library_configured")) {
+        showTemporaryNotification("No enabled music folders to scan");
+        return;
+      }
+      showTemporaryNotification(`Error rescanning library: ${errorMessage(error)}`);
+    }
+  };
+
+  const cancelScan = async () => {
+    try {
+      await invoke("cancel_scan");
+    } catch (error) {
+      console.error("Failed to cancel scan:", error);
+      showTemporaryNotification(`Error cancelling scan: ${errorMessage(error)}`);
+    }
+  };
+
+  const togglePauseScan = async () => {
+    try {
+      await invoke(scanProgress?.paused ? "resume_scan" : "pause_scan");
+    } catch (error) {
+      console.error("Failed to pause scan:", error);
+      showTemporaryNotification(`Error pausing scan: ${errorMessage(error)}`);
     }
   };
 
   const handleAlbumClick = async (album: Album) => {
     setSelectedAlbum(album);
     try {
-      const songs: SongInfo[] = await invoke("get_album_songs", {
-        albumId: album.id,
-      });
+      // The grid only has a thumbnail, the album view shows the full cover
+      const [fullAlbum, songs, artwork] = await Promise.all([
+        invoke<Album | null>("get_album_by_id", { albumId: album.id }),
+        invoke<SongInfo[]>("get_album_songs", { albumId: album.id }),
+        invoke<AlbumArtwork[]>("get_album_artwork", {
+          albumId: album.id,
+          coverSize: ARTWORK_SIZE,
+        }),
+      ]);
+      if (fullAlbum) {
+        setSelectedAlbum(fullAlbum);
+      }
       setAlbumSongs(songs);
+      setAlbumArtwork(artwork);
     } catch (error) {
       console.error("Failed to get album songs:", error);
-      showTemporaryNotification(`Error fetching album songs: ${error}`);
+      showTemporaryNotification(`Error fetching album songs: ${errorMessage(error)}`);
+    }
+  };
+
+  const showArtists = async () => {
+    setLibraryView("artists");
+    setSelectedAlbum(null);
+    try {
+      const fetchedArtists: Artist[] = await invoke("get_artists", {
+        limit: 500,
+        offset: 0,
+      });
+      setArtists(fetchedArtists);
+    } catch (error) {
+      console.error("Failed to fetch artists:", error);
+      showTemporaryNotification(`Error fetching artists: ${errorMessage(error)}`);
+    }
+  };
+
+  const showAlbums = () => {
+    setLibraryView("albums");
+    setSelectedAlbum(null);
+    setSelectedArtist(null);
+    setSelectedGenre(null);
+  };
+
+  const loadGenres = async () => {
+    try {
+      const fetchedGenres: Genre[] = await invoke("get_genres");
+      setGenres(fetchedGenres);
+      return fetchedGenres;
+    } catch (error) {
+      console.error("Failed to fetch genres:", error);
+      showTemporaryNotification(`Error fetching genres: ${errorMessage(error)}`);
+      return [];
+    }
+  };
+
+  const showGenres = async () => {
+    setLibraryView("genres");
+    setSelectedAlbum(null);
+    setSelectedGenre(null);
+    await loadGenres();
+  };
+
+  const handleGenreClick = async (genre: Genre) => {
+    setSelectedGenre(genre);
+    try {
+      const albums: Album[] = await invoke("get_genre_albums", {
+        genreId: genre.id,
+        coverSize: GRID_COVER_SIZE,
+      });
+      setGenreAlbums(albums);
+    } catch (error) {
+      console.error("Failed to get genre:", error);
+      showTemporaryNotification(`Error fetching genre: ${errorMessage(error)}`);
+    }
+  };
+
+  const handleAddGenre = async (name: string) => {
+    try {
+      await invoke("add_genre", { name });
+      await loadGenres();
+    } catch (error) {
+      console.error("Failed to add genre:", error);
+      showTemporaryNotification(`Error adding genre: ${errorMessage(error)}`);
     }
   };
 
+  const handleGenreParentChange = async (parentId: number | null) => {
+    if (!selectedGenre) return;
+    try {
+      await invoke("set_genre_parent", { genreId: selectedGenre.id, parentId });
+      const updated = await loadGenres();
+      setSelectedGenre(updated.find((g) => g.id === selectedGenre.id) ?? null);
+    } catch (error) {
+      console.error("Failed to set parent genre:", error);
+      showTemporaryNotification(`Error setting parent genre: ${errorMessage(error)}`);
+    }
+  };
+
+  const handleBackToGenres = () => {
+    setSelectedGenre(null);
+    setGenreAlbums([]);
+  };
+
+  const handleArtistClick = async (artist: Artist) => {
+    setSelectedArtist(artist);
+    try {
+      const [albums, tracks] = await Promise.all([
+        invoke<Album[]>("get_artist_albums", {
+          artistId: artist.id,
+          coverSize: GRID_COVER_SIZE,
+        }),
+        invoke<SongInfo[]>("get_artist_tracks", { artistId: artist.id }),
+      ]);
+      setArtistAlbums(albums);
+      setArtistTracks(tracks);
+    } catch (error) {
+      console.error("Failed to get artist:", error);
+      showTemporaryNotification(`Error fetching artist: ${errorMessage(error)}`);
+    }
+  };
+
+  const handleSearchArtistClick = async (artist: Artist) => {
+    setLibraryView("artists");
+    await handleArtistClick(artist);
+  };
+
+  const handleSearchSongClick = async (song: SongInfo) => {
+    if (song.album_id === undefined) return;
+    try {
+      const album = await invoke<Album | null>("get_album_by_id", { albumId: song.album_id });
+      if (album) {
+        await handleAlbumClick(album);
+      }
+    } catch (error) {
+      console.error("Failed to get album:", error);
+      showTemporaryNotification(`Error fetching album: ${errorMessage(error)}`);
+    }
+  };
+
+  const handleBackToArtists = () => {
+    setSelectedArtist(null);
+    setArtistAlbums([]);
+    setArtistTracks([]);
+  };
+
   const handleBackToAlbums = () => {
     setSelectedAlbum(null);
     setAlbumSongs([]);
+    setAlbumArtwork([]);
   };
 
   const handleSearchChange = (event: React.ChangeEvent<HTMLInputElement>) => {
@@ -179,19 +505,43 @@ function App() {
   };
 
   const performSearch = useCallback(async () => {
+    setQueryError(null);
     if (searchQuery.trim() === "") {
-      setSearchResults([]);
+      setSearchResults(null);
       return;
     }
     try {
-      const results: Album[] = await invoke("search_albums", {
-        query: searchQuery,
-        limit: 20,
-      });
-      setSearchResults(results);
+      if (STRUCTURED_QUERY.test(searchQuery)) {
+        const [songs, albums] = await Promise.all([
+          invoke<SongInfo[]>("query_songs", { query: searchQuery, limit: 100 }),
+          invoke<Album[]>("query_albums", {
+            query: searchQuery,
+            limit: 20,
+            coverSize: GRID_COVER_SIZE,
+          }),
+        ]);
+        setSearchResults({
+          songs: songs.map((item) => ({ item, snippet: [] })),
+          albums: albums.map((item) => ({ item, snippet: [] })),
+          artists: [],
+        });
+      } else {
+        const results: SearchResults = await invoke("search", {
+          query: searchQuery,
+          limit: 20,
+          coverSize: GRID_COVER_SIZE,
+        });
+        setSearchResults(results);
+      }
     } catch (error) {
-      console.error("Failed to search albums:", error);
-      showTemporaryNotification(`Error searching albums: ${error}`);
+      // Half-typed queries often don't parse yet, so show why without a popup
+      if (hasErrorCode(error, "query")) {
+        setSearchResults(null);
+        setQueryError(errorMessage(error));
+        return;
+      }
+      console.error("Failed to search library:", error);
+      showTemporaryNotification(`Error searching library: ${errorMessage(error)}`);
     }
   }, [searchQuery, showTemporaryNotification]);
 
@@ -210,24 +560,78 @@ function App() {
 
       <h1 className="app-title">MusicThing</h1>
 
-      {!musicFolderPath ? (
-        <Welcome onFolderSelect={selectMusicFolder} />
+      {libraryRoots === null ? null : libraryRoots.length === 0 ? (
+        <Welcome onFolderSelect={addMusicFolder} />
       ) : (
         <>
           <Header 
-            musicFolderPath={musicFolderPath}
+            libraryRoots={libraryRoots}
             isScanning={isScanning}
             scanProgress={scanProgress}
-            onSelectFolder={selectMusicFolder}
-            onScan={() => scanLibrary(musicFolderPath)}
+            onAddFolder={addMusicFolder}
+            onRemoveFolder={removeMusicFolder}
+            onUpdateFolder={updateRootOptions}
+            onScanFolder={(root) => scanRoots([root.id])}
             onRescan={rescanLibrary}
+            onCancelScan={cancelScan}
+            onTogglePauseScan={togglePauseScan}
           />
+          <div className="library-tabs">
+            <button
+              className={`btn ${libraryView === "albums" ? "active" : ""}`}
+              onClick={showAlbums}
+            >
+              Albums
+            </button>
+            <button
+              className={`btn ${libraryView === "artists" ? "active" : ""}`}
+              onClick={showArtists}
+            >
+              Artists
+            </button>
+            <button
+              className={`btn ${libraryView === "genres" ? "active" : ""}`}
+              onClick={showGenres}
+            >
+              Genres
+            </button>
+          </div>
           {selectedAlbum ? (
             <AlbumView 
               selectedAlbum={selectedAlbum} 
               albumSongs={albumSongs} 
+              albumArtwork={albumArtwork}
               onBack={handleBackToAlbums} 
             />
+          ) : libraryView === "artists" ? (
+            selectedArtist ? (
+              <ArtistView
+                artist={selectedArtist}
+                albums={artistAlbums}
+                tracks={artistTracks}
+                onAlbumClick={handleAlbumClick}
+                onBack={handleBackToArtists}
+              />
+            ) : (
+              <ArtistList artists={artists} onArtistClick={handleArtistClick} />
+            )
+          ) : libraryView === "genres" ? (
+            selectedGenre ? (
+              <GenreView
+                genre={selectedGenre}
+                genres={genres}
+                albums={genreAlbums}
+                onAlbumClick={handleAlbumClick}
+                onParentChange={handleGenreParentChange}
+                onBack={handleBackToGenres}
+              />
+            ) : (
+              <GenreList
+                genres={genres}
+                onGenreClick={handleGenreClick}
+                onAddGenre={handleAddGenre}
+              />
+            )
           ) : (
             <MusicLibrary 
               albums={albums} 
@@ -235,6 +639,9 @@ function App() {
               searchQuery={searchQuery}
               onSearchChange={handleSearchChange}
               searchResults={searchResults}
+              queryError={queryError}
+              onSearchSongClick={handleSearchSongClick}
+              onSearchArtistClick={handleSearchArtistClick}
             />
           )}
         </>
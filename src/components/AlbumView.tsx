@@ -1,13 +1,14 @@
 import React from 'react';
-import { Album, SongInfo } from '../types';
+import { Album, AlbumArtwork, SongInfo } from '../types';
 
 interface AlbumViewProps {
   selectedAlbum: Album;
   albumSongs: SongInfo[];
+  albumArtwork: AlbumArtwork[];
   onBack: () => void;
 }
 
-const AlbumView: React.FC<AlbumViewProps> = ({ selectedAlbum, albumSongs, onBack }) => {
+const AlbumView: React.FC<AlbumViewProps> = ({ selectedAlbum, albumSongs, albumArtwork, onBack }) => {
   return (
     <div className="card">
       <button onClick={onBack} className="btn btn-back">
@@ -16,8 +17,8 @@ const AlbumView: React.FC<AlbumViewProps> = ({ selectedAlbum, albumSongs, onBack
       <div className="album-header">
         <img
           src={
-            selectedAlbum.cover_art_base64
-              ? `data:image/jpeg;base64,${selectedAlbum.cover_art_base64}`
+            selectedAlbum.cover_url
+              ? selectedAlbum.cover_url
               : `https://placehold.co/150x150/4A5568/CBD5E0?text=No+Cover`
           }
           alt={selectedAlbum.title}
@@ -35,6 +36,19 @@ const AlbumView: React.FC<AlbumViewProps> = ({ selectedAlbum, albumSongs, onBack
           </div>
         </div>
       </div>
+      {albumArtwork.length > 0 && (
+        <div className="album-artwork">
+          {albumArtwork.map((artwork) => (
+            <img
+              key={artwork.cover_id}
+              src={artwork.url}
+              alt={`${selectedAlbum.title} (${artwork.kind})`}
+              title={artwork.kind}
+              className="album-artwork-image"
+            />
+          ))}
+        </div>
+      )}
       <h3 className="section-title">Songs</h3>
       <div className="song-list">
         {albumSongs.length > 0 ? (
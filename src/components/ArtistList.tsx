@@ -0,0 +1,40 @@
+import React from 'react';
+import { Artist } from '../types';
+
+interface ArtistListProps {
+  artists: Artist[];
+  onArtistClick: (artist: Artist) => void;
+}
+
+const ArtistList: React.FC<ArtistListProps> = ({ artists, onArtistClick }) => {
+  return (
+    <div className="card">
+      <h2 className="section-title">All Artists</h2>
+      {artists.length > 0 ? (
+        <div className="song-list">
+          {artists.map((artist) => (
+            <div
+              key={artist.id}
+              className="song-item artist-item"
+              onClick={() => onArtistClick(artist)}
+            >
+              <div className="song-info">
+                <div>
+                  <p className="song-title">{artist.name}</p>
+                  <p className="song-artist">
+                    {artist.album_count} {artist.album_count === 1 ? "album" : "albums"},{" "}
+                    {artist.track_count} {artist.track_count === 1 ? "track" : "tracks"}
+                  </p>
+                </div>
+              </div>
+            </div>
+          ))}
+        </div>
+      ) : (
+        <p className="empty-message">No artists found.</p>
+      )}
+    </div>
+  );
+};
+
+export default ArtistList;
@@ -0,0 +1,69 @@
+import React from 'react';
+import { Album, Artist, SongInfo } from '../types';
+
+interface ArtistViewProps {
+  artist: Artist;
+  albums: Album[];
+  tracks: SongInfo[];
+  onAlbumClick: (album: Album) => void;
+  onBack: () => void;
+}
+
+const ArtistView: React.FC<ArtistViewProps> = ({ artist, albums, tracks, onAlbumClick, onBack }) => {
+  return (
+    <div className="card">
+      <button onClick={onBack} className="btn btn-back">
+        ← Back to Artists
+      </button>
+      <h2>{artist.name}</h2>
+      <h3 className="section-title">Albums</h3>
+      <div className="album-grid">
+        {albums.map((album) => (
+          <div
+            key={album.id}
+            className="album-card"
+            onClick={() => onAlbumClick(album)}
+          >
+            <img
+              src={
+                album.cover_url
+                  ? album.cover_url
+                  : `https://placehold.co/200x200/4A5568/CBD5E0?text=No+Cover`
+              }
+              alt={album.title}
+              className="album-cover"
+            />
+            <div className="album-info">
+              <h3>{album.title}</h3>
+              <div>
+                <p>{album.artist}</p>
+                <p className="album-year">{album.year}</p>
+              </div>
+            </div>
+          </div>
+        ))}
+      </div>
+      <h3 className="section-title">Tracks</h3>
+      <div className="song-list">
+        {tracks.map((song) => (
+          <div key={song.id} className="song-item">
+            <div className="song-info">
+              <div>
+                <p className="song-title">{song.title}</p>
+                <p className="song-artist">{song.album}</p>
+              </div>
+            </div>
+            <span className="song-duration">
+              {Math.floor(song.duration / 60)}:
+              {Math.floor(song.duration % 60)
+                .toString()
+                .padStart(2, "0")}
+            </span>
+          </div>
+        ))}
+      </div>
+    </div>
+  );
+};
+
+export default ArtistView;
@@ -0,0 +1,78 @@
+import React, { useState } from 'react';
+import { Genre } from '../types';
+
+interface GenreListProps {
+  genres: Genre[];
+  onGenreClick: (genre: Genre) => void;
+  onAddGenre: (name: string) => void;
+}
+
+/** Genres in name order, each followed by its subgenres */
+const sortAsTree = (genres: Genre[]): { genre: Genre; depth: number }[] => {
+  const sorted: { genre: Genre; depth: number }[] = [];
+  const add = (parentId: number | null, depth: number) => {
+    for (const genre of genres.filter((g) => g.parent_id === parentId)) {
+      sorted.push({ genre, depth });
+      add(genre.id, depth + 1);
+    }
+  };
+  add(null, 0);
+  return sorted;
+};
+
+const GenreList: React.FC<GenreListProps> = ({ genres, onGenreClick, onAddGenre }) => {
+  const [newGenre, setNewGenre] = useState("");
+
+  const addGenre = () => {
+    if (newGenre.trim() !== "") {
+      onAddGenre(newGenre.trim());
+      setNewGenre("");
+    }
+  };
+
+  return (
+    <div className="card">
+      <h2 className="section-title">All Genres</h2>
+      <div className="genre-add">
+        <input
+          type="text"
+          value={newGenre}
+          onChange={(e) => setNewGenre(e.target.value)}
+          onKeyDown={(e) => e.key === "Enter" && addGenre()}
+          className="search-input"
+          placeholder="New parent genre, e.g. Metal"
+        />
+        <button onClick={addGenre} className="btn">
+          Add
+        </button>
+      </div>
+      {genres.length > 0 ? (
+        <div className="song-list">
+          {sortAsTree(genres).map(({ genre, depth }) => (
+            <div
+              key={genre.id}
+              className="song-item artist-item"
+              style={{ paddingLeft: `${depth * 1.5}rem` }}
+              onClick={() => onGenreClick(genre)}
+            >
+              <div className="song-info">
+                <div>
+                  <p className="song-title">{genre.name}</p>
+                  <p className="song-artist">
+                    {genre.album_count} {genre.album_count === 1 ? "album" : "albums"},{" "}
+                    {genre.track_count} {genre.track_count === 1 ? "track" : "tracks"},{" "}
+                    {(genre.total_duration / 60).toFixed(0)} min
+                  </p>
+                </div>
+              </div>
+            </div>
+          ))}
+        </div>
+      ) : (
+        <p className="empty-message">No genres found.</p>
+      )}
+    </div>
+  );
+};
+
+export default GenreList;
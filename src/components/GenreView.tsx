@@ -0,0 +1,74 @@
+import React from 'react';
+import { Album, Genre } from '../types';
+
+interface GenreViewProps {
+  genre: Genre;
+  genres: Genre[];
+  albums: Album[];
+  onAlbumClick: (album: Album) => void;
+  onParentChange: (parentId: number | null) => void;
+  onBack: () => void;
+}
+
+const GenreView: React.FC<GenreViewProps> = ({
+  genre,
+  genres,
+  albums,
+  onAlbumClick,
+  onParentChange,
+  onBack,
+}) => {
+  return (
+    <div className="card">
+      <button onClick={onBack} className="btn btn-back">
+        ← Back to Genres
+      </button>
+      <h2>{genre.name}</h2>
+      <label className="genre-parent">
+        Filed under{" "}
+        <select
+          value={genre.parent_id ?? ""}
+          onChange={(e) => onParentChange(e.target.value === "" ? null : Number(e.target.value))}
+        >
+          <option value="">(top level)</option>
+          {genres
+            .filter((g) => g.id !== genre.id)
+            .map((g) => (
+              <option key={g.id} value={g.id}>
+                {g.name}
+              </option>
+            ))}
+        </select>
+      </label>
+      <h3 className="section-title">Albums</h3>
+      <div className="album-grid">
+        {albums.map((album) => (
+          <div
+            key={album.id}
+            className="album-card"
+            onClick={() => onAlbumClick(album)}
+          >
+            <img
+              src={
+                album.cover_url
+                  ? album.cover_url
+                  : `https://placehold.co/200x200/4A5568/CBD5E0?text=No+Cover`
+              }
+              alt={album.title}
+              className="album-cover"
+            />
+            <div className="album-info">
+              <h3>{album.title}</h3>
+              <div>
+                <p>{album.artist}</p>
+                <p className="album-year">{album.year}</p>
+              </div>
+            </div>
+          </div>
+        ))}
+      </div>
+    </div>
+  );
+};
+
+export default GenreView;
@@ -1,52 +1,123 @@
 import React from 'react';
+import { LibraryRoot, ScanProgress } from '../types';
 
 interface HeaderProps {
-  musicFolderPath: string | null;
+  libraryRoots: LibraryRoot[];
   isScanning: boolean;
-  scanProgress: number;
-  onSelectFolder: () => void;
-  onScan: () => void;
+  scanProgress: ScanProgress | null;
+  onAddFolder: () => void;
+  onRemoveFolder: (root: LibraryRoot) => void;
+  onUpdateFolder: (root: LibraryRoot) => void;
+  onScanFolder: (root: LibraryRoot) => void;
   onRescan: () => void;
+  onCancelScan: () => void;
+  onTogglePauseScan: () => void;
 }
 
-const Header: React.FC<HeaderProps> = ({ 
-  musicFolderPath,
+const PHASE_LABELS: Record<ScanProgress["phase"], string> = {
+  idle: "Scanning...",
+  discovering: "Finding files...",
+  reading_tags: "Reading tags...",
+  writing_database: "Saving...",
+};
+
+const formatEta = (seconds: number) => {
+  const minutes = Math.floor(seconds / 60);
+  return minutes > 0
+    ? `${minutes}m ${Math.round(seconds % 60)}s left`
+    : `${Math.round(seconds)}s left`;
+};
+
+const Header: React.FC<HeaderProps> = ({
+  libraryRoots,
   isScanning,
   scanProgress,
-  onSelectFolder,
-  onScan,
-  onRescan 
+  onAddFolder,
+  onRemoveFolder,
+  onUpdateFolder,
+  onScanFolder,
+  onRescan,
+  onCancelScan,
+  onTogglePauseScan
 }) => {
   return (
     <div className="card">
       <h2 className="section-title">Settings</h2>
+      {libraryRoots.map((root) => (
+        <div key={root.id} className="settings-controls">
+          <input
+            type="text"
+            readOnly
+            value={root.online ? root.path : `${root.path} (offline)`}
+            className="folder-input"
+          />
+          <label>
+            <input
+              type="checkbox"
+              checked={root.enabled}
+              onChange={(e) => onUpdateFolder({ ...root, enabled: e.target.checked })}
+            />
+            Enabled
+          </label>
+          <label>
+            <input
+              type="checkbox"
+              checked={root.watch}
+              onChange={(e) => onUpdateFolder({ ...root, watch: e.target.checked })}
+            />
+            Watch
+          </label>
+          <button
+            onClick={() => onScanFolder(root)}
+            disabled={isScanning}
+            className={`btn btn-scan ${isScanning ? "disabled" : ""}`}
+          >
+            Scan
+          </button>
+          <button
+            onClick={() => onRemoveFolder(root)}
+            disabled={isScanning}
+            className="btn btn-folder"
+          >
+            Remove
+          </button>
+        </div>
+      ))}
       <div className="settings-controls">
-        <input
-          type="text"
-          readOnly
-          value={musicFolderPath || "No music folder selected"}
-          className="folder-input"
-        />
-        <button onClick={onSelectFolder} className="btn btn-folder">
-          Select Folder
-        </button>
-        <button
-          onClick={onScan}
-          disabled={isScanning || !musicFolderPath}
-          className={`btn btn-scan ${isScanning ? "disabled" : ""}`}
-        >
-          {isScanning
-            ? `Scanning... ${scanProgress.toFixed(1)}%`
-            : "Scan Library"}
+        <button onClick={onAddFolder} className="btn btn-folder">
+          Add Folder
         </button>
         <button
           onClick={onRescan}
-          disabled={isScanning || !musicFolderPath}
+          disabled={isScanning || libraryRoots.length === 0}
           className={`btn btn-rescan ${isScanning ? "disabled" : ""}`}
         >
-          Rescan Library
+          {isScanning
+            ? `${scanProgress?.paused ? "Paused" : PHASE_LABELS[scanProgress?.phase ?? "idle"]} ${(scanProgress?.progress ?? 0).toFixed(1)}%`
+            : "Rescan Library"}
         </button>
+        {isScanning && (
+          <>
+            <button onClick={onTogglePauseScan} className="btn btn-folder">
+              {scanProgress?.paused ? "Resume" : "Pause"}
+            </button>
+            <button onClick={onCancelScan} className="btn btn-folder">
+              Cancel
+            </button>
+          </>
+        )}
       </div>
+      {isScanning && scanProgress && (
+        <div className="scan-details">
+          {scanProgress.processed_files != null &&
+            scanProgress.total_files != null &&
+            `${scanProgress.processed_files} / ${scanProgress.total_files} files`}
+          {scanProgress.eta_seconds != null &&
+            !scanProgress.paused &&
+            ` · ${formatEta(scanProgress.eta_seconds)}`}
+          {` · ${scanProgress.added} added, ${scanProgress.updated} updated, ${scanProgress.removed} removed`}
+        </div>
+      )}
     </div>
   );
 };
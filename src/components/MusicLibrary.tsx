@@ -1,12 +1,16 @@
 import React from 'react';
-import { Album } from '../types';
+import { Album, Artist, SearchResults, SongInfo } from '../types';
+import Snippet from './Snippet';
 
 interface MusicLibraryProps {
   albums: Album[];
   onAlbumClick: (album: Album) => void;
   searchQuery: string;
   onSearchChange: (event: React.ChangeEvent<HTMLInputElement>) => void;
-  searchResults: Album[];
+  searchResults: SearchResults | null;
+  queryError: string | null;
+  onSearchSongClick: (song: SongInfo) => void;
+  onSearchArtistClick: (artist: Artist) => void;
 }
 
 const MusicLibrary: React.FC<MusicLibraryProps> = ({ 
@@ -14,49 +18,119 @@ const MusicLibrary: React.FC<MusicLibraryProps> = ({
   onAlbumClick,
   searchQuery,
   onSearchChange,
-  searchResults
+  searchResults,
+  queryError,
+  onSearchSongClick,
+  onSearchArtistClick
 }) => {
+  const hasResults =
+    searchResults !== null &&
+    (searchResults.songs.length > 0 ||
+      searchResults.albums.length > 0 ||
+      searchResults.artists.length > 0);
+
   return (
     <div className="card">
-      <h2 className="section-title">Search Albums</h2>
+      <h2 className="section-title">Search Library</h2>
       <input
         type="text"
         value={searchQuery}
         onChange={onSearchChange}
         className="search-input"
-        placeholder="Search by album title or artist..."
+        placeholder='Search, or filter like artist:"Radiohead" year:1995..2000 -label:XL'
       />
-      {searchQuery.trim() !== "" && (
-        <div className="album-grid">
-          {searchResults.length > 0 ? (
-            searchResults.map((album) => (
-              <div
-                key={album.id}
-                className="album-card"
-                onClick={() => onAlbumClick(album)}
-              >
-                <img
-                  src={
-                    album.cover_art_base64
-                      ? `data:image/jpeg;base64,${album.cover_art_base64}`
-                      : `https://placehold.co/200x200/4A5568/CBD5E0?text=No+Cover`
-                  }
-                  alt={album.title}
-                  className="album-cover"
-                />
-                <div className="album-info">
-                  <h3>{album.title}</h3>
-                  <div>
-                    <p>{album.artist}</p>
-                    <p className="album-year">{album.year}</p>
-                  </div>
+      {queryError && <p className="query-error">{queryError}</p>}
+      {searchQuery.trim() !== "" && !queryError && (
+        hasResults && searchResults ? (
+          <>
+            {searchResults.artists.length > 0 && (
+              <>
+                <h3 className="section-title">Artists</h3>
+                <div className="song-list">
+                  {searchResults.artists.map(({ item: artist, snippet }) => (
+                    <div
+                      key={artist.id}
+                      className="song-item artist-item"
+                      onClick={() => onSearchArtistClick(artist)}
+                    >
+                      <div className="song-info">
+                        <p className="song-title">
+                          <Snippet parts={snippet} />
+                        </p>
+                      </div>
+                    </div>
+                  ))}
                 </div>
-              </div>
-            ))
-          ) : (
-            <p className="empty-message">No albums found.</p>
-          )}
-        </div>
+              </>
+            )}
+            {searchResults.albums.length > 0 && (
+              <>
+                <h3 className="section-title">Albums</h3>
+                <div className="album-grid">
+                  {searchResults.albums.map(({ item: album, snippet }) => (
+                    <div
+                      key={album.id}
+                      className="album-card"
+                      onClick={() => onAlbumClick(album)}
+                    >
+                      <img
+                        src={
+                          album.cover_url
+                            ? album.cover_url
+                            : `https://placehold.co/200x200/4A5568/CBD5E0?text=No+Cover`
+                        }
+                        alt={album.title}
+                        className="album-cover"
+                      />
+                      <div className="album-info">
+                        <h3>{album.title}</h3>
+                        <div>
+                          <p>{album.artist}</p>
+                          <p className="album-year">{album.year}</p>
+                          {snippet.length > 0 && (
+                            <p className="album-year">
+                              <Snippet parts={snippet} />
+                            </p>
+                          )}
+                        </div>
+                      </div>
+                    </div>
+                  ))}
+                </div>
+              </>
+            )}
+            {searchResults.songs.length > 0 && (
+              <>
+                <h3 className="section-title">Songs</h3>
+                <div className="song-list">
+                  {searchResults.songs.map(({ item: song, snippet }) => (
+                    <div
+                      key={song.id}
+                      className="song-item artist-item"
+                      onClick={() => onSearchSongClick(song)}
+                    >
+                      <div className="song-info">
+                        <div>
+                          <p className="song-title">{song.title}</p>
+                          <p className="song-artist">
+                            {song.artist} · {song.album}
+                          </p>
+                          {snippet.length > 0 && (
+                            <p className="song-artist">
+                              <Snippet parts={snippet} />
+                            </p>
+                          )}
+                        </div>
+                      </div>
+                    </div>
+                  ))}
+                </div>
+              </>
+            )}
+          </>
+        ) : (
+          <p className="empty-message">Nothing found.</p>
+        )
       )}
 
       {searchQuery.trim() === "" && (
@@ -72,8 +146,8 @@ const MusicLibrary: React.FC<MusicLibraryProps> = ({
                 >
                   <img
                     src={
-                      album.cover_art_base64
-                        ? `data:image/jpeg;base64,${album.cover_art_base64}`
+                      album.cover_url
+                        ? album.cover_url
                         : `https://placehold.co/200x200/4A5568/CBD5E0?text=No+Cover`
                     }
                     alt={album.title}
@@ -0,0 +1,15 @@
+import React from 'react';
+import { SnippetPart } from '../types';
+
+/** Text around a search match, with the matched words highlighted */
+const Snippet: React.FC<{ parts: SnippetPart[] }> = ({ parts }) => {
+  return (
+    <span className="search-snippet">
+      {parts.map((part, index) =>
+        part.matched ? <mark key={index}>{part.text}</mark> : <span key={index}>{part.text}</span>
+      )}
+    </span>
+  );
+};
+
+export default Snippet;
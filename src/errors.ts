@@ -0,0 +1,35 @@
+export type AppErrorCode =
+  | "database"
+  | "migration"
+  | "io"
+  | "tag"
+  | "image"
+  | "not_found"
+  | "invalid_input"
+  | "query"
+  | "scan_in_progress"
+  | "no_scan_in_progress"
+  | "analysis_in_progress"
+  | "no_library_configured"
+  | "settings"
+  | "watcher"
+  | "player"
+  | "task";
+
+/** Error returned by a backend command */
+export interface AppError {
+  code: AppErrorCode;
+  message: string;
+}
+
+export const isAppError = (error: unknown): error is AppError =>
+  typeof error === "object" &&
+  error !== null &&
+  "code" in error &&
+  "message" in error;
+
+export const hasErrorCode = (error: unknown, code: AppErrorCode) =>
+  isAppError(error) && error.code === code;
+
+export const errorMessage = (error: unknown) =>
+  isAppError(error) ? error.message : String(error);
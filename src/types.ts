@@ -1,5 +1,6 @@
 export interface SongInfo {
   id?: number;
+  album_id?: number;
   title: string;
   artist: string;
   album: string;
@@ -7,11 +8,16 @@ export interface SongInfo {
   duration: number;
   path: string;
   lyrics_path?: string;
-  cover_art_base64?: string;
   album_artist?: string;
   year?: string;
   label?: string;
   track_number?: string;
+  format?: string;
+  codec?: string;
+  replaygain_track_gain?: number;
+  replaygain_track_peak?: number;
+  replaygain_album_gain?: number;
+  replaygain_album_peak?: number;
 }
 
 export interface Album {
@@ -20,8 +26,102 @@ export interface Album {
   artist: string;
   year?: string;
   genre?: string;
-  cover_art_base64?: string;
+  cover_id?: number | null;
+  cover_url?: string | null;
   song_count: number;
   total_duration: number;
   folder_path: string;
-}
\ No newline at end of file
+}
+
+export interface Artist {
+  id: number;
+  name: string;
+  sort_name: string;
+  album_count: number;
+  track_count: number;
+}
+
+export interface Genre {
+  id: number;
+  name: string;
+  parent_id: number | null;
+  album_count: number;
+  track_count: number;
+  total_duration: number;
+}
+
+export interface SnippetPart {
+  text: string;
+  matched: boolean;
+}
+
+export interface SearchHit<T> {
+  item: T;
+  snippet: SnippetPart[];
+}
+
+export interface SearchResults {
+  songs: SearchHit<SongInfo>[];
+  albums: SearchHit<Album>[];
+  artists: SearchHit<Artist>[];
+}
+
+export interface TagSplitting {
+  artist_delimiters: string[];
+  genre_delimiters: string[];
+  exceptions: string[];
+}
+
+export type ArtworkKind = "front" | "back" | "booklet" | "artist" | "other";
+
+/** A picture of an album besides its cover */
+export interface AlbumArtwork {
+  cover_id: number;
+  kind: ArtworkKind;
+  width: number | null;
+  height: number | null;
+  url: string;
+}
+
+export type NormalizationMode = "off" | "track" | "album";
+
+export interface LibraryRoot {
+  id: number;
+  path: string;
+  enabled: boolean;
+  watch: boolean;
+  exclusions: string[];
+  online: boolean;
+}
+
+export interface ScanError {
+  id: number;
+  root_id?: number;
+  path: string;
+  kind: "io" | "corrupt" | "unsupported" | "crash";
+  message: string;
+  created_at: string;
+}
+
+export interface ScanFailure {
+  root_id: number;
+  path: string;
+  message: string;
+}
+
+export type ScanPhase = "idle" | "discovering" | "reading_tags" | "writing_database";
+
+export interface ScanProgress {
+  is_scanning: boolean;
+  paused: boolean;
+  phase: ScanPhase;
+  current_root?: string;
+  progress: number;
+  current_file?: string;
+  total_files?: number;
+  processed_files?: number;
+  eta_seconds?: number;
+  added: number;
+  updated: number;
+  removed: number;
+}